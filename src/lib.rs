@@ -0,0 +1,4 @@
+//! Solutions to the Advent of Code 2019 puzzles along with the utilities they share.
+
+pub mod solutions;
+pub mod utilities;
@@ -2,13 +2,23 @@ use std::io;
 
 use std::io::{BufRead, BufReader};
 
-mod solutions;
-mod utilities;
+use advent_of_code_2019::solutions;
+
+mod tools;
 
 /// Print program usage to stdout
 fn print_usage() {
     println!("Expected usage:");
     println!("  $> <executable> <day num> <input file>");
+    println!("  $> <executable> disasm <intcode file>");
+    println!("  $> <executable> cfg <intcode file>");
+    println!("  $> <executable> transpile <intcode file>");
+    println!("  $> <executable> compile <source file>");
+    println!("  $> <executable> debug <intcode file>");
+    println!("  $> <executable> ascii <intcode file>");
+    println!("  $> <executable> profile <intcode file>");
+    println!("  $> <executable> annotate <intcode file>");
+    println!("  $> <executable> coverage <intcode file>");
 }
 
 fn get_file_input(path: &str) -> io::Result<Vec<String>> {
@@ -22,17 +32,29 @@ fn main() {
         print_usage();
         return;
     }
-    let day: u8 = match args[0].parse() {
+    let input = match get_file_input(&args[1]) {
         Ok(x) => x,
         Err(err) => {
-            println!("Error while parsing <day>: {:?}", err);
+            println!("Error while getting input: {:?}", err);
             return;
         }
     };
-    let input = match get_file_input(&args[1]) {
+    match args[0].as_str() {
+        "disasm" => return tools::disasm(&input),
+        "cfg" => return tools::cfg(&input),
+        "transpile" => return tools::transpile(&input),
+        "compile" => return tools::compile(&input),
+        "debug" => return tools::debug(&input),
+        "ascii" => return tools::ascii(&input),
+        "profile" => return tools::profile(&input, false),
+        "annotate" => return tools::profile(&input, true),
+        "coverage" => return tools::coverage(&input),
+        _ => (),
+    }
+    let day: u8 = match args[0].parse() {
         Ok(x) => x,
         Err(err) => {
-            println!("Error while getting input: {:?}", err);
+            println!("Error while parsing <day>: {:?}", err);
             return;
         }
     };
@@ -63,9 +85,6 @@ fn main() {
         23 => unimplemented!(),
         24 => unimplemented!(),
         25 => unimplemented!(),
-        _ => {
-            print_usage();
-            return;
-        }
+        _ => print_usage(),
     }
 }
@@ -1,16 +1,16 @@
-pub fn work(lines: &Vec<String>) {
-    println!("Part 1: {}", do_work(&lines));
-    println!("Part 2: {}", do_work_2(&lines));
+pub fn work(lines: &[String]) {
+    println!("Part 1: {}", do_work(lines));
+    println!("Part 2: {}", do_work_2(lines));
 }
 
-fn do_work(lines: &Vec<String>) -> usize {
+fn do_work(lines: &[String]) -> usize {
     lines
         .iter()
         .map(|line| mass_to_fuel(line.parse().unwrap()))
         .sum()
 }
 
-fn do_work_2(lines: &Vec<String>) -> usize {
+fn do_work_2(lines: &[String]) -> usize {
     lines
         .iter()
         .map(|line| line.parse::<usize>().unwrap())
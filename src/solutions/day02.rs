@@ -1,33 +1,37 @@
-use crate::utilities::intcode::interpret;
+use crate::utilities::intcode::{interpret, Machine, Memory, State};
 
-pub fn work(lines: &Vec<String>) {
-    println!("Part 1: {}", do_work(&lines));
-    println!("Part 2: {}", do_work_2(&lines));
+pub fn work(lines: &[String]) {
+    println!("Part 1: {}", do_work(lines));
+    println!("Part 2: {}", do_work_2(lines));
 }
 
-fn do_work(lines: &Vec<String>) -> isize {
-    let mut program: Vec<isize> = lines[0]
+fn do_work(lines: &[String]) -> isize {
+    let mut program: Memory = lines[0]
         .split(',')
         .map(|piece| piece.parse().unwrap())
         .collect();
     // set program up for 1202 program
     program[1] = 12;
     program[2] = 2;
-    interpret(&mut program, (), ())
+    interpret(&mut program, (), ()).unwrap()
 }
 
-fn do_work_2(lines: &Vec<String>) -> isize {
-    let program: Vec<isize> = lines[0]
+fn do_work_2(lines: &[String]) -> isize {
+    let program: Memory = lines[0]
         .split(',')
         .map(|piece| piece.parse().unwrap())
         .collect();
+    // decode once, every attempt below starts from a copy
+    let mut template = Machine::new(program);
+    template.predecode();
     for i in 0..99 {
         for k in 0..99 {
-            let mut tmp_mem = program.clone();
-            tmp_mem[1] = i; // noun
-            tmp_mem[2] = k; // verb
-            let res = interpret(&mut tmp_mem, (), ());
-            if res == 19690720 {
+            let mut machine = template.clone();
+            machine.set_memory(1, i); // noun
+            machine.set_memory(2, k); // verb
+
+            // some combinations produce invalid programs, those just aren't the answer
+            if machine.run() == Ok(State::Halted) && machine.memory()[0] == 19690720 {
                 return i * 100 + k;
             }
         }
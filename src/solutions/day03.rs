@@ -1,16 +1,16 @@
 use std::collections::HashMap;
 
-pub fn work(lines: &Vec<String>) {
-    println!("Part 1: {}", do_work(&lines));
-    println!("Part 2: {}", do_work_2(&lines));
+pub fn work(lines: &[String]) {
+    println!("Part 1: {}", do_work(lines));
+    println!("Part 2: {}", do_work_2(lines));
 }
 
-fn do_work(lines: &Vec<String>) -> usize {
+fn do_work(lines: &[String]) -> usize {
     assert_eq!(lines.len(), 2);
     let wire_1_positions = draw_wire(&lines[0]);
     let wire_2_positions = draw_wire(&lines[1]);
 
-    let mut min_dist = std::usize::MAX;
+    let mut min_dist = usize::MAX;
     for pos in wire_1_positions.keys() {
         if wire_2_positions.contains_key(pos) {
             let dist = (pos.0.abs() + pos.1.abs()) as usize;
@@ -23,12 +23,12 @@ fn do_work(lines: &Vec<String>) -> usize {
     min_dist
 }
 
-fn do_work_2(lines: &Vec<String>) -> usize {
+fn do_work_2(lines: &[String]) -> usize {
     assert_eq!(lines.len(), 2);
     let wire_1_positions = draw_wire(&lines[0]);
     let wire_2_positions = draw_wire(&lines[1]);
 
-    let mut min_length = std::usize::MAX;
+    let mut min_length = usize::MAX;
     for (pos, len) in wire_1_positions.iter() {
         if wire_2_positions.contains_key(pos) {
             let length = len + wire_2_positions.get(pos).unwrap();
@@ -98,7 +98,7 @@ mod test {
 
     #[test]
     fn part_1_examples() {
-        let examples = vec![
+        let examples = [
             vec!["R8,U5,L5,D3".to_owned(), "U7,R6,D4,L4".to_owned()],
             vec![
                 "R75,D30,R83,U83,L12,D49,R71,U7,L72".to_owned(),
@@ -109,7 +109,7 @@ mod test {
                 "U98,R91,D20,R16,D67,R40,U7,R15,U6,R7".to_owned(),
             ],
         ];
-        let expected = vec![6, 159, 135];
+        let expected = [6, 159, 135];
 
         for i in 0..examples.len() {
             assert_eq!(do_work(&examples[i]), expected[i]);
@@ -118,7 +118,7 @@ mod test {
 
     #[test]
     fn part_2_examples() {
-        let examples = vec![
+        let examples = [
             vec!["R8,U5,L5,D3".to_owned(), "U7,R6,D4,L4".to_owned()],
             vec![
                 "R75,D30,R83,U83,L12,D49,R71,U7,L72".to_owned(),
@@ -129,7 +129,7 @@ mod test {
                 "U98,R91,D20,R16,D67,R40,U7,R15,U6,R7".to_owned(),
             ],
         ];
-        let expected = vec![30, 610, 410];
+        let expected = [30, 610, 410];
 
         for i in 0..examples.len() {
             assert_eq!(do_work_2(&examples[i]), expected[i]);
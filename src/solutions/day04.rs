@@ -1,4 +1,4 @@
-pub fn work(_lines: &Vec<String>) {
+pub fn work(_lines: &[String]) {
     let lower = 264360;
     let upper = 746325;
     println!("Part 1: {}", find_pass(lower, upper));
@@ -43,7 +43,7 @@ fn is_pass_2(pass_num: usize) -> bool {
     let pass = pass.as_bytes();
 
     let alw_inc = pass.windows(2).all(|w| w[0] <= w[1]);
-    if (pass.len() == 6 && alw_inc) == false {
+    if !(pass.len() == 6 && alw_inc) {
         return false;
     }
 
@@ -51,10 +51,8 @@ fn is_pass_2(pass_num: usize) -> bool {
         if pass[i] != pass[i + 1] {
             continue;
         }
-        if i > 1 {
-            if pass[i - 1] == pass[i] {
-                continue;
-            }
+        if i > 1 && pass[i - 1] == pass[i] {
+            continue;
         }
         if let Some(val) = pass.get(i + 2) {
             if *val == pass[i] {
@@ -63,7 +61,7 @@ fn is_pass_2(pass_num: usize) -> bool {
         }
         return true;
     }
-    return false;
+    false
 }
 
 #[cfg(test)]
@@ -72,15 +70,15 @@ mod test {
 
     #[test]
     fn pass_example_check() {
-        assert_eq!(is_pass(111111), true);
-        assert_eq!(is_pass(223450), false);
-        assert_eq!(is_pass(123789), false);
+        assert!(is_pass(111111));
+        assert!(!is_pass(223450));
+        assert!(!is_pass(123789));
     }
 
     #[test]
     fn pass_2_example_check() {
-        assert_eq!(is_pass_2(112233), true);
-        assert_eq!(is_pass_2(123444), false);
-        assert_eq!(is_pass_2(111122), true);
+        assert!(is_pass_2(112233));
+        assert!(!is_pass_2(123444));
+        assert!(is_pass_2(111122));
     }
 }
@@ -1,17 +1,17 @@
-use crate::utilities::intcode::interpret;
+use crate::utilities::intcode::{interpret, Memory};
 
-pub fn work(lines: &Vec<String>) {
+pub fn work(lines: &[String]) {
     println!("{:#?}", do_work(&lines[0]));
 }
 
 fn do_work(line: &str) -> Vec<isize> {
-    let mut program: Vec<isize> = line
+    let mut program: Memory = line
         .trim()
         .split(',')
         .map(|piece| piece.parse().unwrap())
         .collect();
     let mut output = Vec::new();
-    interpret(&mut program, 1, &mut output);
+    interpret(&mut program, 1, &mut output).unwrap();
     output
 }
 
@@ -23,7 +23,7 @@ mod test {
     fn part_1_test() {
         let input = include_str!("../../inputs/day05.txt");
         println!("{:?}", input);
-        let exp_out = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 8332629];
+        let exp_out = vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 8332629];
         let output = do_work(input);
         assert_eq!(output, exp_out);
     }
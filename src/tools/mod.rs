@@ -0,0 +1,182 @@
+//! Command line tools for working with IntCode programs, as opposed to solving a given day.
+
+use std::io::{self, BufRead, Read, Write};
+
+use advent_of_code_2019::utilities::intcode::ascii;
+use advent_of_code_2019::utilities::intcode::cfg::Graph;
+use advent_of_code_2019::utilities::intcode::compile;
+use advent_of_code_2019::utilities::intcode::debugger::Debugger;
+use advent_of_code_2019::utilities::intcode::transpile;
+use advent_of_code_2019::utilities::intcode::{disasm, IterInput, Machine, Memory};
+
+/// Parse the first line of an input file as an IntCode program, reporting any problems
+fn load_program(lines: &[String]) -> Option<Memory> {
+    let line = match lines.first() {
+        Some(line) => line,
+        None => {
+            println!("Input file is empty");
+            return None;
+        }
+    };
+    match line.parse() {
+        Ok(mem) => Some(mem),
+        Err(err) => {
+            println!("Error while parsing program: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Print an annotated disassembly of the program
+pub fn disasm(lines: &[String]) {
+    if let Some(mem) = load_program(lines) {
+        print!("{}", disasm::listing(mem.as_slice()));
+    }
+}
+
+/// Print the program's control-flow graph in Graphviz DOT format
+pub fn cfg(lines: &[String]) {
+    if let Some(mem) = load_program(lines) {
+        print!("{}", Graph::build(mem.as_slice()).to_dot());
+    }
+}
+
+/// Print the program translated into a Rust module, to be added to the crate
+pub fn transpile(lines: &[String]) {
+    if let Some(mem) = load_program(lines) {
+        match transpile::transpile(mem.as_slice()) {
+            Ok(source) => print!("{}", source),
+            Err(err) => println!("Can't translate program: {}", err),
+        }
+    }
+}
+
+/// Compile a program written in the `intcode::compile` language, printing it as IntCode
+pub fn compile(lines: &[String]) {
+    match compile::compile(&lines.join("\n")) {
+        Ok(program) => {
+            let words: Vec<String> = program.iter().map(|word| word.to_string()).collect();
+            println!("{}", words.join(","));
+        }
+        Err(err) => println!("Can't compile program: {}", err),
+    }
+}
+
+/// Interactive step debugger, reading commands from stdin until `q` or end of input.
+///
+/// Commands can be piped in to script a session, see `intcode::debugger` for the command list.
+pub fn debug(lines: &[String]) {
+    let mem = match load_program(lines) {
+        Some(mem) => mem,
+        None => return,
+    };
+    let mut dbg = Debugger::new(Machine::new(mem));
+    let mut last_command = String::from("s");
+
+    println!("{}", dbg.execute("l 1"));
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        // an empty line repeats the previous command
+        let command = match line.trim() {
+            "" => last_command.clone(),
+            command => command.to_owned(),
+        };
+        match command.as_str() {
+            "q" => break,
+            "h" => println!("commands: s [n], sb [n], rw <step>, lw <addr>, c, b/db <addr>, w/dw <addr>, i <v,..>, r, m <addr> [n], l [n], o, q"),
+            _ => println!("{}", dbg.execute(&command)),
+        }
+        last_command = command;
+    }
+}
+
+/// Run an ASCII speaking program with stdin and stdout attached
+pub fn ascii(lines: &[String]) {
+    if let Some(mem) = load_program(lines) {
+        let stdin = io::stdin();
+        if let Err(err) = ascii::interactive(&mut Machine::new(mem), stdin.lock(), io::stdout()) {
+            println!("Program failed: {}", err);
+        }
+    }
+}
+
+/// Read input values for a program from stdin, separated by commas or whitespace
+fn read_values() -> Option<Vec<isize>> {
+    let mut text = String::new();
+    if let Err(err) = io::stdin().read_to_string(&mut text) {
+        println!("Error while reading input: {:?}", err);
+        return None;
+    }
+    let values: Result<Vec<isize>, _> = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|val| !val.is_empty())
+        .map(|val| val.parse())
+        .collect();
+    match values {
+        Ok(values) => Some(values),
+        Err(err) => {
+            println!("Error while parsing input: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Run the program with profiling on and print where it spent its time.
+///
+/// Input values are read from stdin, separated by commas or whitespace. With `annotate` the whole
+/// program is listed with execution counts, otherwise just a summary of the busiest parts.
+pub fn profile(lines: &[String], annotate: bool) {
+    let mem = match load_program(lines) {
+        Some(mem) => mem,
+        None => return,
+    };
+    let values = match read_values() {
+        Some(values) => values,
+        None => return,
+    };
+
+    let mut machine = Machine::new(mem.clone());
+    machine.set_profiling(true);
+    let mut output = Vec::new();
+    if let Err(err) = machine.run_with(IterInput(values.into_iter()), &mut output) {
+        println!("Program failed: {}", err);
+    }
+    println!("output: {:?}\n", output);
+
+    let profile = machine.profile().unwrap();
+    match annotate {
+        true => print!("{}", profile.annotate(mem.as_slice())),
+        false => print!("{}", profile.report(mem.as_slice(), 10)),
+    }
+}
+
+/// Run the program with coverage on and list it, marking code which never ran.
+///
+/// Input values are read from stdin as for `profile`. The listing is of memory as the program left
+/// it, so self-modified instructions show up as they were executed.
+pub fn coverage(lines: &[String]) {
+    let mem = match load_program(lines) {
+        Some(mem) => mem,
+        None => return,
+    };
+    let values = match read_values() {
+        Some(values) => values,
+        None => return,
+    };
+
+    let mut machine = Machine::new(mem);
+    machine.set_coverage(true);
+    let mut output = Vec::new();
+    if let Err(err) = machine.run_with(IterInput(values.into_iter()), &mut output) {
+        println!("Program failed: {}", err);
+    }
+    println!("output: {:?}\n", output);
+
+    let mem = machine.memory().as_slice();
+    print!("{}", machine.coverage().unwrap().annotate(mem));
+}
@@ -0,0 +1,217 @@
+//! Chains of amplifiers, each running a copy of the same program (day 7).
+//!
+//! Every amplifier is given its phase setting as its first input. The first amplifier then gets
+//! the signal 0, and each amplifier's output becomes the next amplifier's input. With feedback
+//! wiring the last amplifier's output is routed back into the first until every amplifier halts.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+
+use super::{IntcodeError, Machine, State};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wiring {
+    /// Signal passes through each amplifier once
+    Serial,
+    /// Last amplifier feeds back into the first
+    Feedback,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AmpError {
+    /// Amplifier number `amp` (counting from 0) failed
+    Intcode { amp: usize, err: IntcodeError },
+    /// The chain finished without the last amplifier producing a signal
+    NoSignal,
+}
+
+impl fmt::Display for AmpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmpError::Intcode { amp, err } => write!(f, "amplifier {} failed: {}", amp, err),
+            AmpError::NoSignal => write!(f, "last amplifier produced no signal"),
+        }
+    }
+}
+
+impl Error for AmpError {}
+
+/// Highest signal found by `find_best`, and the phase settings producing it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Best {
+    pub signal: isize,
+    pub phases: Vec<isize>,
+}
+
+/// Run one amplifier per phase setting and return the final signal sent to the thrusters
+pub fn run_chain(program: &[isize], phases: &[isize], wiring: Wiring) -> Result<isize, AmpError> {
+    let mut amps = vec![Machine::new(program); phases.len()];
+    let mut queues: Vec<VecDeque<isize>> = phases.iter().map(|p| vec![*p].into()).collect();
+    match queues.first_mut() {
+        Some(queue) => queue.push_back(0),
+        None => return Err(AmpError::NoSignal),
+    }
+
+    let mut signal = None;
+    let mut halted = vec![false; amps.len()];
+    while halted.iter().any(|h| !h) {
+        let mut progress = false;
+        for i in 0..amps.len() {
+            if halted[i] {
+                continue;
+            }
+            let mut output = Vec::new();
+            let state = amps[i]
+                .run_with(&mut queues[i], &mut output)
+                .map_err(|err| AmpError::Intcode { amp: i, err })?;
+            halted[i] = state == State::Halted;
+            progress |= halted[i] || !output.is_empty();
+
+            if i + 1 < amps.len() {
+                queues[i + 1].extend(output);
+            } else if let Some(last) = output.last() {
+                signal = Some(*last);
+                if wiring == Wiring::Feedback {
+                    queues[0].extend(output);
+                }
+            }
+        }
+
+        if !progress {
+            // everyone left is waiting on input which will never come
+            let amp = halted.iter().position(|h| !h).unwrap();
+            let err = amps[amp].run_with((), ()).unwrap_err();
+            return Err(AmpError::Intcode { amp, err });
+        }
+    }
+
+    signal.ok_or(AmpError::NoSignal)
+}
+
+/// Try every ordering of `phase_values`, returning the one producing the highest signal
+pub fn find_best(
+    program: &[isize],
+    phase_values: &[isize],
+    wiring: Wiring,
+) -> Result<Best, AmpError> {
+    let mut best: Option<Best> = None;
+    for phases in permutations(phase_values) {
+        let signal = run_chain(program, &phases, wiring)?;
+        // `Option::is_none_or` would need Rust 1.82
+        #[allow(clippy::unnecessary_map_or)]
+        let better = best.as_ref().map_or(true, |b| signal > b.signal);
+        if better {
+            best = Some(Best { signal, phases });
+        }
+    }
+    best.ok_or(AmpError::NoSignal)
+}
+
+/// Every ordering of `items`
+fn permutations(items: &[isize]) -> Vec<Vec<isize>> {
+    if items.len() <= 1 {
+        return vec![items.to_vec()];
+    }
+    let mut result = Vec::new();
+    for i in 0..items.len() {
+        let mut rest = items.to_vec();
+        let first = rest.remove(i);
+        for mut perm in permutations(&rest) {
+            perm.insert(0, first);
+            result.push(perm);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serial_examples() {
+        let examples = [
+            (
+                vec![
+                    3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+                ],
+                43210,
+                vec![4, 3, 2, 1, 0],
+            ),
+            (
+                vec![
+                    3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23,
+                    23, 4, 23, 99, 0, 0,
+                ],
+                54321,
+                vec![0, 1, 2, 3, 4],
+            ),
+            (
+                vec![
+                    3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33, 1002, 33, 7,
+                    33, 1, 33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0,
+                ],
+                65210,
+                vec![1, 0, 4, 3, 2],
+            ),
+        ];
+        for (program, signal, phases) in examples.iter() {
+            assert_eq!(run_chain(program, phases, Wiring::Serial), Ok(*signal));
+            assert_eq!(
+                find_best(program, &[0, 1, 2, 3, 4], Wiring::Serial),
+                Ok(Best {
+                    signal: *signal,
+                    phases: phases.clone()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn feedback_examples() {
+        let examples = [
+            (
+                vec![
+                    3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001,
+                    28, -1, 28, 1005, 28, 6, 99, 0, 0, 5,
+                ],
+                139629729,
+                vec![9, 8, 7, 6, 5],
+            ),
+            (
+                vec![
+                    3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26,
+                    1001, 54, -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55,
+                    2, 53, 55, 53, 4, 53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+                ],
+                18216,
+                vec![9, 7, 8, 5, 6],
+            ),
+        ];
+        for (program, signal, phases) in examples.iter() {
+            assert_eq!(run_chain(program, phases, Wiring::Feedback), Ok(*signal));
+            assert_eq!(
+                find_best(program, &[5, 6, 7, 8, 9], Wiring::Feedback),
+                Ok(Best {
+                    signal: *signal,
+                    phases: phases.clone()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn stalled_chain() {
+        // each amplifier wants three inputs but only ever gets two
+        let program = vec![3, 0, 3, 0, 3, 0, 99];
+        assert_eq!(
+            run_chain(&program, &[0, 1], Wiring::Serial),
+            Err(AmpError::Intcode {
+                amp: 0,
+                err: IntcodeError::InputExhausted { ip: 4, word: 3 }
+            })
+        );
+        assert_eq!(permutations(&[1, 2, 3]).len(), 6);
+    }
+}
@@ -0,0 +1,184 @@
+//! Adapters for programs which talk in ASCII text.
+//!
+//! Several puzzles read commands a line at a time and print text, usually finishing with a single
+//! large number which is the actual answer.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{BufRead, Write};
+
+use super::{Input, IntcodeError, Machine, Output, Poll, State};
+
+/// Feeds text to a program as character codes, one newline terminated line at a time
+#[derive(Clone, Debug, Default)]
+pub struct AsciiInput {
+    pending: VecDeque<isize>,
+}
+
+impl AsciiInput {
+    /// Input consisting of `text`, with a trailing newline added if missing
+    pub fn new(text: &str) -> Self {
+        let mut input = AsciiInput::default();
+        input.push_line(text);
+        input
+    }
+
+    /// Queue up another line, with a trailing newline added if missing
+    pub fn push_line(&mut self, line: &str) {
+        self.pending.extend(line.bytes().map(isize::from));
+        if !line.ends_with('\n') {
+            self.pending.push_back(isize::from(b'\n'));
+        }
+    }
+}
+
+impl Input for AsciiInput {
+    fn get_word(&mut self) -> isize {
+        self.next_input()
+            .expect("Program requested input, but there was no text left")
+    }
+
+    fn next_input(&mut self) -> Option<isize> {
+        self.pending.pop_front()
+    }
+
+    /// More lines can be pushed, so running out of text is only pending
+    fn poll_input(&mut self) -> Poll {
+        match self.pending.pop_front() {
+            Some(val) => Poll::Ready(val),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Input for &mut AsciiInput {
+    fn get_word(&mut self) -> isize {
+        (**self).get_word()
+    }
+
+    fn next_input(&mut self) -> Option<isize> {
+        (**self).next_input()
+    }
+
+    fn poll_input(&mut self) -> Poll {
+        (**self).poll_input()
+    }
+}
+
+/// Collects text written by a program.
+///
+/// Values outside the ASCII range can't be text, the last such value is kept in `value` instead.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AsciiOutput {
+    pub text: String,
+    pub value: Option<isize>,
+}
+
+fn as_ascii(val: isize) -> Option<char> {
+    match u8::try_from(val) {
+        Ok(byte) if byte.is_ascii() => Some(char::from(byte)),
+        _ => None,
+    }
+}
+
+impl Output for &mut AsciiOutput {
+    fn write_word(&mut self, val: isize) {
+        match as_ascii(val) {
+            Some(c) => self.text.push(c),
+            None => self.value = Some(val),
+        }
+    }
+}
+
+/// Run `machine` interactively, feeding it lines from `reader` and writing its text to `writer`.
+///
+/// Non-ASCII values are written as numbers on their own line. Reaching the end of `reader` while
+/// the program wants input is reported as `IntcodeError::InputExhausted`.
+pub fn interactive(
+    machine: &mut Machine,
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+) -> Result<(), IntcodeError> {
+    let mut input = AsciiInput::default();
+    loop {
+        let mut output = Vec::new();
+        let state = machine.run_with(&mut input, &mut output)?;
+        for val in output {
+            match as_ascii(val) {
+                Some(c) => write!(writer, "{}", c),
+                None => writeln!(writer, "{}", val),
+            }
+            .expect("Failed to write program output");
+        }
+        if state == State::Halted {
+            return Ok(());
+        }
+
+        writer.flush().expect("Failed to write program output");
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            // nothing left to give, so let the machine report it
+            return machine.run_with((), ()).map(|_| ());
+        }
+        input.push_line(&line);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{asm::assemble, interpret, Memory};
+    use super::*;
+
+    /// Reads lines, printing each back in upper case, until an empty line is given. Then prints
+    /// the number of lines read as a digit followed by the number 1000.
+    fn shout() -> Vec<isize> {
+        assemble(
+            "
+            line:   IN [ch]
+                    EQ [ch], #10, [flag]
+                    JT [flag], #empty
+            loop:   ADD [ch], #-32, [ch]
+                    OUT [ch]
+                    IN [ch]
+                    EQ [ch], #10, [flag]
+                    JF [flag], #loop
+                    OUT #10
+                    ADD [count], #1, [count]
+                    JT #1, #line
+            empty:  ADD [count], #48, [ch]
+                    OUT [ch]
+                    OUT #1000
+                    HALT
+            ch:     .data 0
+            flag:   .data 0
+            count:  .data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut input = AsciiInput::new("abc");
+        input.push_line("hi\n");
+        input.push_line("");
+        let mut output = AsciiOutput::default();
+        interpret(&mut Memory::from(shout()), input, &mut output).unwrap();
+        assert_eq!(output.text, "ABC\nHI\n2");
+        assert_eq!(output.value, Some(1000));
+    }
+
+    #[test]
+    fn interactive_session() {
+        let mut machine = Machine::new(shout());
+        let mut screen = Vec::new();
+        interactive(&mut machine, "north\nsouth\n\n".as_bytes(), &mut screen).unwrap();
+        assert_eq!(String::from_utf8(screen).unwrap(), "NORTH\nSOUTH\n21000\n");
+
+        let mut machine = Machine::new(shout());
+        assert!(matches!(
+            interactive(&mut machine, "west\n".as_bytes(), Vec::new()),
+            Err(IntcodeError::InputExhausted { .. })
+        ));
+    }
+}
@@ -0,0 +1,283 @@
+//! Assembler turning mnemonic source into an IntCode program.
+//!
+//! The syntax matches the output of the disassembler, so listings can be edited and reassembled:
+//!
+//! ```text
+//! ; comments run from a semicolon to the end of the line
+//! start:  IN [check+1]          ; labels end with a colon
+//! check:  JT #0, #done          ; `#` immediate, `[..]` position, `rb+..` relative
+//!         ARB #-2
+//!         ADD rb+1, [value], [value]
+//! done:   HALT
+//! value:  .data 7, 8, start     ; raw words, labels resolve to their address
+//! ```
+//!
+//! Parameters may be numbers, labels or a label plus/minus a number. A line may start with a
+//! numeric address such as `12:`, which must match the address the line assembles to; this allows
+//! a disassembly listing to be fed straight back in. `DATA` is accepted as a synonym for `.data`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use super::{AddrMode, OpCode};
+
+/// Problem found while assembling, `line` counts from 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Word whose value may not be known until every label has been seen
+enum Value {
+    Num(isize),
+    Label {
+        name: String,
+        offset: isize,
+        line: usize,
+    },
+}
+
+/// Assemble source text into a program ready to be loaded into memory
+pub fn assemble(src: &str) -> Result<Vec<isize>, AsmError> {
+    let mut words = Vec::new();
+    let mut labels = HashMap::new();
+
+    for (idx, raw_line) in src.lines().enumerate() {
+        let line = idx + 1;
+        let err = |message: String| AsmError { line, message };
+        let mut rest = raw_line.split(';').next().unwrap_or("").trim();
+
+        // peel off any labels and address markers
+        while let Some((head, tail)) = rest.split_once(':') {
+            let head = head.trim();
+            if let Ok(addr) = head.parse::<usize>() {
+                if addr != words.len() {
+                    return Err(err(format!(
+                        "line is marked as address {}, but assembles to {}",
+                        addr,
+                        words.len()
+                    )));
+                }
+            } else if is_identifier(head) {
+                if labels.insert(head.to_owned(), words.len()).is_some() {
+                    return Err(err(format!("label '{}' defined more than once", head)));
+                }
+            } else {
+                return Err(err(format!("invalid label '{}'", head)));
+            }
+            rest = tail.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (name, operands) = match rest.split_once(char::is_whitespace) {
+            Some((name, operands)) => (name, operands.trim()),
+            None => (rest, ""),
+        };
+        let operands: Vec<&str> = if operands.is_empty() {
+            Vec::new()
+        } else {
+            operands.split(',').map(str::trim).collect()
+        };
+
+        if name.eq_ignore_ascii_case(".data") || name.eq_ignore_ascii_case("data") {
+            if operands.is_empty() {
+                return Err(err("data directive needs at least one value".to_owned()));
+            }
+            for operand in operands {
+                words.push(parse_value(operand, line)?);
+            }
+            continue;
+        }
+
+        let op = OpCode::from_mnemonic(name)
+            .ok_or_else(|| err(format!("unknown mnemonic '{}'", name)))?;
+        if operands.len() != op.num_params() {
+            return Err(err(format!(
+                "{} takes {} parameters, found {}",
+                op.mnemonic(),
+                op.num_params(),
+                operands.len()
+            )));
+        }
+
+        let mut word = 0;
+        let mut params = Vec::new();
+        for (i, operand) in operands.iter().enumerate() {
+            let (mode, value) = parse_operand(operand, line)?;
+            if mode == AddrMode::Imm && op.write_param() == Some(i + 1) {
+                return Err(err(format!(
+                    "parameter {} of {} is written to and can't be immediate",
+                    i + 1,
+                    op.mnemonic()
+                )));
+            }
+            word += mode as isize * 10isize.pow(i as u32 + 2);
+            params.push(value);
+        }
+        words.push(Value::Num(word + op as isize));
+        words.extend(params);
+    }
+
+    words
+        .into_iter()
+        .map(|word| match word {
+            Value::Num(num) => Ok(num),
+            Value::Label { name, offset, line } => match labels.get(&name) {
+                Some(addr) => Ok(*addr as isize + offset),
+                None => Err(AsmError {
+                    line,
+                    message: format!("undefined label '{}'", name),
+                }),
+            },
+        })
+        .collect()
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Parse a number, label, or label with a numeric offset
+fn parse_value(text: &str, line: usize) -> Result<Value, AsmError> {
+    if let Ok(num) = text.parse() {
+        return Ok(Value::Num(num));
+    }
+
+    let (name, offset) = match text.rfind(['+', '-']) {
+        Some(idx) => {
+            let offset = text[idx..].trim_start_matches('+').trim().parse();
+            (text[..idx].trim(), offset.ok())
+        }
+        None => (text, Some(0)),
+    };
+    match offset {
+        Some(offset) if is_identifier(name) => Ok(Value::Label {
+            name: name.to_owned(),
+            offset,
+            line,
+        }),
+        _ => Err(AsmError {
+            line,
+            message: format!("invalid value '{}'", text),
+        }),
+    }
+}
+
+/// Parse an instruction parameter along with its addressing mode sigil
+fn parse_operand(text: &str, line: usize) -> Result<(AddrMode, Value), AsmError> {
+    if let Some(inner) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Ok((AddrMode::Pos, parse_value(inner.trim(), line)?))
+    } else if let Some(value) = text.strip_prefix('#') {
+        Ok((AddrMode::Imm, parse_value(value.trim(), line)?))
+    } else if let Some(offset) = text.strip_prefix("rb") {
+        let offset = offset.trim();
+        let value = match offset.strip_prefix('+') {
+            _ if offset.is_empty() => Value::Num(0),
+            Some(positive) => parse_value(positive.trim(), line)?,
+            None if offset.starts_with('-') => parse_value(offset, line)?,
+            None => {
+                return Err(AsmError {
+                    line,
+                    message: format!("invalid relative parameter '{}'", text),
+                })
+            }
+        };
+        Ok((AddrMode::Rel, value))
+    } else {
+        Err(AsmError {
+            line,
+            message: format!("parameter '{}' needs an addressing mode", text),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::day05;
+    use super::super::disasm::listing;
+    use super::*;
+
+    #[test]
+    fn matches_hand_encoded_jump_test() {
+        // immediate mode jump program from the day 5 jump tests, outputs 0 if input is 0 else 1
+        let src = "
+                IN [check+1]        ; overwrite the jump condition
+        check:  JT #-1, #nonzero
+                ADD #0, #0, [result]
+        nonzero:
+                OUT [result]
+                HALT
+        result: .data 1
+        ";
+        assert_eq!(
+            assemble(src),
+            Ok(vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1])
+        );
+    }
+
+    #[test]
+    fn relative_operands() {
+        assert_eq!(
+            assemble("arb #10\nadd rb-1, rb, rb+2\nDATA -4, end\nend: halt"),
+            Ok(vec![109, 10, 22201, -1, 0, 2, -4, 8, 99])
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let line_of = |src| assemble(src).unwrap_err().line;
+        assert_eq!(line_of("HALT\nJMP #0"), 2);
+        assert_eq!(line_of("ADD #1, #2"), 1);
+        assert_eq!(line_of("ADD #1, #2, #3"), 1);
+        assert_eq!(line_of("OUT 5"), 1);
+        assert_eq!(line_of("\n\nOUT [missing]"), 3);
+        assert_eq!(line_of("a: HALT\na: HALT"), 2);
+        assert_eq!(line_of("HALT\n0: HALT"), 2);
+    }
+
+    #[test]
+    fn round_trips_through_disassembler() {
+        let programs = [
+            day05(),
+            vec![
+                109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+            ],
+            vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+        ];
+        for prog in programs.iter() {
+            assert_eq!(&assemble(&listing(prog)).unwrap(), prog);
+        }
+    }
+
+    #[test]
+    fn round_trips_any_instruction_word() {
+        // every opcode and mode digit combination, including digits an instruction doesn't use
+        // and digits above the modes, followed by enough parameters for any instruction
+        let words = (0..100_000).chain((1..10).map(|high| high * 100_000 + 1101));
+        for word in words {
+            let prog = vec![word, 5, 6, 7, 99];
+            assert_eq!(assemble(&listing(&prog)).unwrap(), prog, "word {}", word);
+        }
+        for prog in [vec![1104, 5, 99], vec![10099], vec![100001, 0, 0, 0, 99]].iter() {
+            assert_eq!(&assemble(&listing(prog)).unwrap(), prog);
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Control-flow graph of an IntCode program, found by static analysis.
+//!
+//! Starting from address 0 (or other chosen entry points), every instruction reachable by falling
+//! through or by a jump with an immediate mode target is decoded. The instructions are then split
+//! into basic blocks, which begin at the entry points, at jump targets, and after conditional
+//! jumps. Jumps through a position or relative mode parameter can't be resolved statically and are
+//! recorded as unknown edges.
+//!
+//! Only the initial memory image is analysed, so self-modifying code can take paths the graph
+//! doesn't show.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{disasm, parse_instruction, AddrMode, OpCode, Word};
+
+/// Where control can go after a block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// Execution continues at the next address, including when a conditional jump isn't taken
+    Fallthrough(usize),
+    /// Jump to an immediate mode target
+    Jump(usize),
+    /// Jump to a target only known at run time
+    Unknown,
+}
+
+/// Straight line run of instructions with a single entry and exit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    /// Address of the first instruction
+    pub start: usize,
+    /// Address just past the last instruction
+    pub end: usize,
+    /// Each instruction's address and assembly text
+    pub instructions: Vec<(usize, String)>,
+    /// Empty when the block ends in a HALT
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Graph {
+    /// Blocks keyed by start address
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+/// A single reachable instruction
+struct Instruction {
+    text: String,
+    len: usize,
+    // whether the instruction ends a block whatever follows it
+    branch: bool,
+    edges: Vec<Edge>,
+}
+
+/// Decode the instruction at `addr` and work out where control goes after it
+fn analyse(mem: &[isize], addr: usize) -> Option<Instruction> {
+    let (text, len) = disasm::decode(mem, addr)?;
+    let (op, mode1, mode2, _) = parse_instruction(mem[addr].to_i128()).ok()?;
+    let next = addr + len;
+    let (branch, edges) = match op {
+        OpCode::Halt => (true, Vec::new()),
+        OpCode::JmpIfTrue | OpCode::JmpIfFalse => {
+            let jump = match mode2 {
+                AddrMode::Imm if mem[addr + 2] >= 0 => Edge::Jump(mem[addr + 2] as usize),
+                _ => Edge::Unknown,
+            };
+            // an immediate condition always goes the same way
+            let taken = match mode1 {
+                AddrMode::Imm => Some((mem[addr + 1] != 0) == (op == OpCode::JmpIfTrue)),
+                _ => None,
+            };
+            let edges = match taken {
+                Some(true) => vec![jump],
+                Some(false) => vec![Edge::Fallthrough(next)],
+                None => vec![jump, Edge::Fallthrough(next)],
+            };
+            (true, edges)
+        }
+        _ => (false, vec![Edge::Fallthrough(next)]),
+    };
+    Some(Instruction {
+        text,
+        len,
+        branch,
+        edges,
+    })
+}
+
+impl Graph {
+    pub fn build(mem: &[isize]) -> Graph {
+        Graph::build_from(mem, &[0])
+    }
+
+    /// Graph of the code reachable from any of `entries`, e.g. to include subroutine return
+    /// addresses only ever jumped to through memory
+    pub fn build_from(mem: &[isize], entries: &[usize]) -> Graph {
+        // find every reachable instruction
+        let mut reached = BTreeMap::new();
+        let mut todo = entries.to_vec();
+        while let Some(addr) = todo.pop() {
+            if reached.contains_key(&addr) {
+                continue;
+            }
+            if let Some(inst) = analyse(mem, addr) {
+                for edge in inst.edges.iter() {
+                    match edge {
+                        Edge::Fallthrough(next) | Edge::Jump(next) => todo.push(*next),
+                        Edge::Unknown => (),
+                    }
+                }
+                reached.insert(addr, inst);
+            }
+        }
+
+        let mut leaders: BTreeSet<usize> = entries.iter().copied().collect();
+        for inst in reached.values().filter(|inst| inst.branch) {
+            for edge in inst.edges.iter() {
+                match edge {
+                    Edge::Fallthrough(next) | Edge::Jump(next) => leaders.insert(*next),
+                    Edge::Unknown => false,
+                };
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        for start in leaders.iter().filter(|addr| reached.contains_key(addr)) {
+            let mut addr = *start;
+            let mut instructions = Vec::new();
+            let edges = loop {
+                let inst = &reached[&addr];
+                instructions.push((addr, inst.text.clone()));
+                addr += inst.len;
+                if inst.branch || leaders.contains(&addr) || !reached.contains_key(&addr) {
+                    break inst.edges.clone();
+                }
+            };
+            let block = Block {
+                start: *start,
+                end: addr,
+                instructions,
+                edges,
+            };
+            blocks.insert(*start, block);
+        }
+        Graph { blocks }
+    }
+
+    /// Block containing the instruction starting at `addr`
+    pub fn block_at(&self, addr: usize) -> Option<&Block> {
+        let (_, block) = self.blocks.range(..=addr).next_back()?;
+        match block.instructions.iter().any(|(a, _)| *a == addr) {
+            true => Some(block),
+            false => None,
+        }
+    }
+
+    /// Render the graph in Graphviz DOT format.
+    ///
+    /// Edges to addresses which don't hold a valid instruction lead to a dashed "invalid" node,
+    /// and unknown edges to a "?" node of their own.
+    pub fn to_dot(&self) -> String {
+        let mut out =
+            String::from("digraph intcode {\n    node [shape=box, fontname=\"monospace\"];\n");
+        let mut invalid = BTreeSet::new();
+        for block in self.blocks.values() {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|(addr, text)| format!("{}: {}\\l", addr, escape(text)))
+                .collect();
+            out += &format!("    b{} [label=\"{}\"];\n", block.start, label);
+            for edge in block.edges.iter() {
+                let (target, attrs) = match edge {
+                    Edge::Fallthrough(addr) => (*addr, ""),
+                    Edge::Jump(addr) => (*addr, " [label=\"jump\"]"),
+                    Edge::Unknown => {
+                        out += &format!(
+                            "    u{0} [label=\"?\", shape=circle];\n    b{0} -> u{0} [style=dashed];\n",
+                            block.start
+                        );
+                        continue;
+                    }
+                };
+                if !self.blocks.contains_key(&target) {
+                    invalid.insert(target);
+                }
+                out += &format!("    b{} -> b{}{};\n", block.start, target, attrs);
+            }
+        }
+        for addr in invalid {
+            out += &format!("    b{0} [label=\"{0}: invalid\", style=dashed];\n", addr);
+        }
+        out += "}\n";
+        out
+    }
+}
+
+/// Escape text for use inside a quoted DOT label
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::asm::assemble;
+    use super::super::day05;
+    use super::*;
+
+    #[test]
+    fn countdown_loop() {
+        let program = assemble(
+            "
+                    IN [count]
+            loop:   OUT [count]
+                    ADD [count], #-1, [count]
+                    JT [count], #loop
+                    JT #1, #done
+                    OUT #99
+            done:   JF #1, #0
+                    JT [count], [count]
+                    HALT
+            count:  .data 0
+            ",
+        )
+        .unwrap();
+        let graph = Graph::build(&program);
+        let edges: Vec<(usize, Vec<Edge>)> = graph
+            .blocks
+            .values()
+            .map(|block| (block.start, block.edges.clone()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (0, vec![Edge::Fallthrough(2)]),
+                (2, vec![Edge::Jump(2), Edge::Fallthrough(11)]),
+                (11, vec![Edge::Jump(16)]),
+                // the OUT #99 at 14 is never reached
+                (16, vec![Edge::Fallthrough(19)]),
+                (19, vec![Edge::Unknown, Edge::Fallthrough(22)]),
+                (22, vec![]),
+            ]
+        );
+        assert_eq!(graph.blocks[&2].end, 11);
+        assert_eq!(graph.block_at(4).map(|block| block.start), Some(2));
+        assert_eq!(graph.block_at(14), None);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph intcode {\n"));
+        assert!(dot.contains(
+            "    b2 [label=\"2: OUT [23]\\l4: ADD [23], #-1, [23]\\l8: JT [23], #2\\l\"];\n"
+        ));
+        assert!(dot.contains("    b2 -> b2 [label=\"jump\"];\n    b2 -> b11;\n"));
+        assert!(dot.contains("    b19 -> u19 [style=dashed];\n"));
+    }
+
+    #[test]
+    fn day5_program() {
+        let mut program = day05();
+        // the program adds the system ID to the word at 6, so analyse it as it is after reading
+        // the ID 5, which turns that word into a jump to the second set of tests
+        program[6] += 5;
+        let graph = Graph::build(&program);
+        // the self-test jumps to 99999 when a check fails
+        assert!(graph
+            .blocks
+            .values()
+            .any(|block| block.edges.contains(&Edge::Jump(99999))));
+        assert!(graph
+            .to_dot()
+            .contains("    b99999 [label=\"99999: invalid\", style=dashed];\n"));
+    }
+}
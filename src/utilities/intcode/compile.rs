@@ -0,0 +1,1121 @@
+//! Compiler for a tiny language, as an easier way than assembly to write IntCode test programs.
+//!
+//! ```text
+//! // outputs the sum of the squares of the values read, stopping at 0
+//! fn square(x) {
+//!     return x * x;
+//! }
+//!
+//! let total = 0;
+//! let n = input();
+//! while n != 0 {
+//!     total = total + square(n);
+//!     n = input();
+//! }
+//! output(total);
+//! ```
+//!
+//! Every value is a word. There are `+`, `-` and `*`, comparisons `<`, `<=`, `>`, `>=`, `==` and
+//! `!=` giving 1 or 0, and `!`, `&&` and `||` which treat any non-zero value as true and
+//! short-circuit. `input()` reads a value and `output(x)` writes one. Variables declared with
+//! `let` outside of a function are globals, those declared in a function are local to the
+//! enclosing block. Functions may be recursive and return 0 if they end without a `return`.
+//! Comments run from `//` to the end of the line.
+//!
+//! Top level statements run in order, then the program halts. Each call gets a stack frame
+//! addressed through the relative base, holding the return address at `rb+0`, then the
+//! parameters, locals and temporaries. The callee leaves its result in `rb+1`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+
+use super::asm::assemble;
+
+/// Problem found while compiling, `line` counts from 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompileError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for CompileError {}
+
+fn error<T>(line: usize, message: String) -> Result<T, CompileError> {
+    Err(CompileError { line, message })
+}
+
+/// Compile `src` into a program ready to be loaded into memory
+pub fn compile(src: &str) -> Result<Vec<isize>, CompileError> {
+    let asm = compile_to_asm(src)?;
+    Ok(assemble(&asm).expect("compiler produced invalid assembly"))
+}
+
+/// Compile `src` into source for `asm::assemble`
+pub fn compile_to_asm(src: &str) -> Result<String, CompileError> {
+    let tokens = lex(src)?;
+    let program = Parser { tokens, pos: 0 }.program()?;
+    Compiler::new(&program)?.program(&program)
+}
+
+// Lexing
+
+const KEYWORDS: [&str; 8] = [
+    "let", "fn", "if", "else", "while", "return", "input", "output",
+];
+
+/// Symbols, longer ones first so they are matched in preference
+const SYMBOLS: [&str; 19] = [
+    "<=", ">=", "==", "!=", "&&", "||", "(", ")", "{", "}", ",", ";", "=", "+", "-", "*", "<", ">",
+    "!",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Num(isize),
+    Ident(String),
+    Sym(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Num(num) => write!(f, "{}", num),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Sym(sym) => write!(f, "{}", sym),
+        }
+    }
+}
+
+/// Split `src` into tokens, each with the line it is on
+fn lex(src: &str) -> Result<Vec<(Token, usize)>, CompileError> {
+    let mut tokens = Vec::new();
+    for (idx, line) in src.lines().enumerate() {
+        let mut rest = line.split("//").next().unwrap_or("").trim_start();
+        while let Some(c) = rest.chars().next() {
+            let len = if c.is_ascii_digit() {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                match rest[..len].parse() {
+                    Ok(num) => tokens.push((Token::Num(num), idx + 1)),
+                    Err(_) => return error(idx + 1, format!("number {} is too big", &rest[..len])),
+                }
+                len
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Ident(rest[..len].to_owned()), idx + 1));
+                len
+            } else {
+                match SYMBOLS.iter().find(|sym| rest.starts_with(*sym)) {
+                    Some(sym) => {
+                        tokens.push((Token::Sym(sym), idx + 1));
+                        sym.len()
+                    }
+                    None => return error(idx + 1, format!("unexpected character '{}'", c)),
+                }
+            };
+            rest = rest[len..].trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+// Parsing
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    Num(isize),
+    Var {
+        name: String,
+        line: usize,
+    },
+    Call {
+        name: String,
+        args: Vec<Expr>,
+        line: usize,
+    },
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Whether evaluating the expression calls a function, which could change a global
+    fn calls_function(&self) -> bool {
+        match self {
+            Expr::Num(_) | Expr::Var { .. } => false,
+            Expr::Call { name, args, .. } => {
+                (name != "input" && name != "output") || args.iter().any(Expr::calls_function)
+            }
+            Expr::Neg(inner) | Expr::Not(inner) => inner.calls_function(),
+            Expr::Binary(_, lhs, rhs) => lhs.calls_function() || rhs.calls_function(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Stmt {
+    Let {
+        name: String,
+        value: Expr,
+    },
+    Assign {
+        name: String,
+        value: Expr,
+        line: usize,
+    },
+    If {
+        cond: Expr,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    While {
+        cond: Expr,
+        body: Vec<Stmt>,
+    },
+    Return {
+        value: Option<Expr>,
+        line: usize,
+    },
+    Expr(Expr),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Stmt>,
+    line: usize,
+}
+
+#[derive(Debug, Default)]
+struct Program {
+    functions: Vec<Function>,
+    main: Vec<Stmt>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    /// Line of the next token, or of the last one at the end of input
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume `sym` if it is next
+    fn eat(&mut self, sym: &str) -> bool {
+        match self.peek() {
+            Some(Token::Sym(next)) if *next == sym => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(name)) if name == keyword)
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, CompileError> {
+        let found = match self.peek() {
+            Some(token) => format!("'{}'", token),
+            None => "end of input".to_owned(),
+        };
+        error(
+            self.line(),
+            format!("expected {}, found {}", expected, found),
+        )
+    }
+
+    fn expect(&mut self, sym: &str) -> Result<(), CompileError> {
+        match self.eat(sym) {
+            true => Ok(()),
+            false => self.unexpected(&format!("'{}'", sym)),
+        }
+    }
+
+    /// Name which isn't a keyword
+    fn name(&mut self) -> Result<String, CompileError> {
+        match self.peek() {
+            Some(Token::Ident(name)) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("a name"),
+        }
+    }
+
+    fn program(mut self) -> Result<Program, CompileError> {
+        let mut program = Program::default();
+        while self.peek().is_some() {
+            if self.is_keyword("fn") {
+                program.functions.push(self.function()?);
+            } else {
+                program.main.push(self.statement()?);
+            }
+        }
+        Ok(program)
+    }
+
+    fn function(&mut self) -> Result<Function, CompileError> {
+        let line = self.line();
+        self.pos += 1;
+        let name = self.name()?;
+        self.expect("(")?;
+        let mut params = Vec::new();
+        if !self.eat(")") {
+            loop {
+                params.push(self.name()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        let body = self.block()?;
+        Ok(Function {
+            name,
+            params,
+            body,
+            line,
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            if self.peek().is_none() {
+                return self.unexpected("'}'");
+            }
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        let line = self.line();
+        let stmt = if self.is_keyword("let") {
+            self.pos += 1;
+            let name = self.name()?;
+            self.expect("=")?;
+            Stmt::Let {
+                name,
+                value: self.expr()?,
+            }
+        } else if self.is_keyword("if") {
+            self.pos += 1;
+            let cond = self.expr()?;
+            let then = self.block()?;
+            let otherwise = if self.is_keyword("else") {
+                self.pos += 1;
+                match self.is_keyword("if") {
+                    true => vec![self.statement()?],
+                    false => self.block()?,
+                }
+            } else {
+                Vec::new()
+            };
+            return Ok(Stmt::If {
+                cond,
+                then,
+                otherwise,
+            });
+        } else if self.is_keyword("while") {
+            self.pos += 1;
+            let cond = self.expr()?;
+            let body = self.block()?;
+            return Ok(Stmt::While { cond, body });
+        } else if self.is_keyword("return") {
+            self.pos += 1;
+            let value = match self.peek() {
+                Some(Token::Sym(";")) => None,
+                _ => Some(self.expr()?),
+            };
+            Stmt::Return { value, line }
+        } else if self.is_keyword("fn") {
+            return error(
+                line,
+                "functions can only be defined at the top level".to_owned(),
+            );
+        } else if let (Some(Token::Ident(name)), Some((Token::Sym("="), _))) =
+            (self.peek(), self.tokens.get(self.pos + 1))
+        {
+            let name = name.clone();
+            self.pos += 2;
+            Stmt::Assign {
+                name,
+                value: self.expr()?,
+                line,
+            }
+        } else {
+            Stmt::Expr(self.expr()?)
+        };
+        self.expect(";")?;
+        Ok(stmt)
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    /// Parse operators binding at least as tightly as `level`, from loosest to tightest
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        const LEVELS: [&[(&str, BinOp)]; 5] = [
+            &[("||", BinOp::Or)],
+            &[("&&", BinOp::And)],
+            &[
+                ("==", BinOp::Equal),
+                ("!=", BinOp::NotEqual),
+                ("<=", BinOp::LessEq),
+                (">=", BinOp::GreaterEq),
+                ("<", BinOp::Less),
+                (">", BinOp::Greater),
+            ],
+            &[("+", BinOp::Add), ("-", BinOp::Sub)],
+            &[("*", BinOp::Mul)],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        loop {
+            let op = LEVELS[level]
+                .iter()
+                .find(|(sym, _)| matches!(self.peek(), Some(Token::Sym(next)) if next == sym));
+            match op {
+                Some((_, op)) => {
+                    self.pos += 1;
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+                }
+                None => return Ok(lhs),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("-") {
+            return Ok(match self.unary()? {
+                Expr::Num(num) => Expr::Num(num.wrapping_neg()),
+                expr => Expr::Neg(Box::new(expr)),
+            });
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let line = self.line();
+        match self.next() {
+            Some(Token::Num(num)) => Ok(Expr::Num(num)),
+            Some(Token::Sym("(")) => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name))
+                if name == "input" || name == "output" || !KEYWORDS.contains(&name.as_str()) =>
+            {
+                if !self.eat("(") {
+                    return Ok(Expr::Var { name, line });
+                }
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expr::Call { name, args, line })
+            }
+            _ => {
+                self.pos -= 1;
+                self.unexpected("an expression")
+            }
+        }
+    }
+}
+
+// Code generation
+
+/// Collect the names declared by `let` anywhere in `stmts`
+fn declared(stmts: &[Stmt], names: &mut BTreeSet<String>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Let { name, .. } => {
+                names.insert(name.clone());
+            }
+            Stmt::If {
+                then, otherwise, ..
+            } => {
+                declared(then, names);
+                declared(otherwise, names);
+            }
+            Stmt::While { body, .. } => declared(body, names),
+            _ => (),
+        }
+    }
+}
+
+/// Mark standing in for the current function's frame size plus `n`, written `$n$`, as the size
+/// isn't known until the whole function has been compiled
+fn frame(n: usize) -> String {
+    format!("${}$", n)
+}
+
+struct Compiler {
+    /// Parameter count of each function
+    functions: HashMap<String, usize>,
+    globals: BTreeSet<String>,
+    labels: usize,
+    lines: Vec<String>,
+    /// Frame slot of each local in scope, `None` when compiling top level statements
+    locals: Option<HashMap<String, usize>>,
+    /// First frame slot not used by a local in scope
+    next_slot: usize,
+    /// Frame size needed so far
+    frame_size: usize,
+}
+
+impl Compiler {
+    fn new(program: &Program) -> Result<Self, CompileError> {
+        let mut functions = HashMap::new();
+        for function in program.functions.iter() {
+            if functions
+                .insert(function.name.clone(), function.params.len())
+                .is_some()
+            {
+                return error(
+                    function.line,
+                    format!("function '{}' defined more than once", function.name),
+                );
+            }
+        }
+        let mut globals = BTreeSet::new();
+        declared(&program.main, &mut globals);
+        Ok(Compiler {
+            functions,
+            globals,
+            labels: 0,
+            lines: Vec::new(),
+            locals: None,
+            next_slot: 1,
+            frame_size: 1,
+        })
+    }
+
+    fn program(mut self, program: &Program) -> Result<String, CompileError> {
+        let mut out = String::from("        ARB #stack\n");
+        self.block(&program.main)?;
+        self.emit("HALT".to_owned());
+        out += &self.finish();
+
+        for function in program.functions.iter() {
+            out += &format!("f_{}:\n", function.name);
+            let mut locals = HashMap::new();
+            for (n, param) in function.params.iter().enumerate() {
+                if locals.insert(param.clone(), n + 1).is_some() {
+                    return error(
+                        function.line,
+                        format!("parameter '{}' appears more than once", param),
+                    );
+                }
+            }
+            self.locals = Some(locals);
+            self.next_slot = function.params.len() + 1;
+            // the result is returned in rb+1, even without parameters
+            self.frame_size = self.next_slot.max(2);
+            self.block(&function.body)?;
+            self.emit("ADD #0, #0, rb+1".to_owned());
+            self.emit("JT #1, rb+0".to_owned());
+            out += &self.finish();
+        }
+
+        for global in self.globals.iter() {
+            out += &format!("g_{}: .data 0\n", global);
+        }
+        out += "stack: .data 0\n";
+        Ok(out)
+    }
+
+    /// Take the code compiled so far, filling in the frame size
+    fn finish(&mut self) -> String {
+        let mut out = String::new();
+        for line in self.lines.drain(..) {
+            let parts: Vec<&str> = line.split('$').collect();
+            out += "        ";
+            for (n, part) in parts.iter().enumerate() {
+                match n % 2 {
+                    0 => out += part,
+                    _ => out += &(self.frame_size + part.parse::<usize>().unwrap()).to_string(),
+                }
+            }
+            out += "\n";
+        }
+        out
+    }
+
+    fn emit(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!("L{}", self.labels)
+    }
+
+    fn place_label(&mut self, label: &str) {
+        self.emit(format!("{}:", label));
+    }
+
+    /// Operand for temporary frame slot `slot`
+    fn temp(&mut self, slot: usize) -> String {
+        self.frame_size = self.frame_size.max(slot + 1);
+        format!("rb+{}", slot)
+    }
+
+    fn variable(&self, name: &str, line: usize) -> Result<String, CompileError> {
+        if let Some(slot) = self.locals.as_ref().and_then(|locals| locals.get(name)) {
+            return Ok(format!("rb+{}", slot));
+        }
+        match self.globals.contains(name) {
+            true => Ok(format!("[g_{}]", name)),
+            false => error(line, format!("unknown variable '{}'", name)),
+        }
+    }
+
+    /// Store `value` into `dest` unless it is already there
+    fn copy(&mut self, value: &str, dest: &str) {
+        if value != dest {
+            self.emit(format!("ADD {}, #0, {}", value, dest));
+        }
+    }
+
+    /// Compile statements in a new scope for locals
+    fn block(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        let (locals, next_slot) = (self.locals.clone(), self.next_slot);
+        for stmt in stmts {
+            self.statement(stmt)?;
+        }
+        self.locals = locals;
+        self.next_slot = next_slot;
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Let { name, value } => {
+                let slot = self.next_slot;
+                let value = self.expr(value, slot)?;
+                let dest = match self.locals.as_mut() {
+                    Some(locals) => {
+                        locals.insert(name.clone(), slot);
+                        self.next_slot += 1;
+                        self.temp(slot)
+                    }
+                    None => format!("[g_{}]", name),
+                };
+                self.copy(&value, &dest);
+            }
+            Stmt::Assign { name, value, line } => {
+                let dest = self.variable(name, *line)?;
+                let value = self.expr(value, self.next_slot)?;
+                self.copy(&value, &dest);
+            }
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let (other, end) = (self.label(), self.label());
+                let cond = self.expr(cond, self.next_slot)?;
+                self.emit(format!("JF {}, #{}", cond, other));
+                self.block(then)?;
+                if !otherwise.is_empty() {
+                    self.emit(format!("JT #1, #{}", end));
+                }
+                self.place_label(&other);
+                if !otherwise.is_empty() {
+                    self.block(otherwise)?;
+                    self.place_label(&end);
+                }
+            }
+            Stmt::While { cond, body } => {
+                let (top, end) = (self.label(), self.label());
+                self.place_label(&top);
+                let cond = self.expr(cond, self.next_slot)?;
+                self.emit(format!("JF {}, #{}", cond, end));
+                self.block(body)?;
+                self.emit(format!("JT #1, #{}", top));
+                self.place_label(&end);
+            }
+            Stmt::Return { value, line } => {
+                if self.locals.is_none() {
+                    return error(*line, "return outside a function".to_owned());
+                }
+                let value = match value {
+                    Some(value) => self.expr(value, self.next_slot)?,
+                    None => "#0".to_owned(),
+                };
+                self.copy(&value, "rb+1");
+                self.emit("JT #1, rb+0".to_owned());
+            }
+            Stmt::Expr(expr) => {
+                self.expr(expr, self.next_slot)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Compile `expr`, returning an operand holding its value. Temporaries are taken from frame
+    /// slot `slot` upwards, a value which needs one ends up in `slot`.
+    fn expr(&mut self, expr: &Expr, slot: usize) -> Result<String, CompileError> {
+        let dest = match expr {
+            Expr::Num(num) => return Ok(format!("#{}", num)),
+            Expr::Var { name, line } => return self.variable(name, *line),
+            Expr::Call { name, args, line } => return self.call(name, args, *line, slot),
+            Expr::Neg(inner) => {
+                let val = self.expr(inner, slot)?;
+                let dest = self.temp(slot);
+                self.emit(format!("MUL {}, #-1, {}", val, dest));
+                dest
+            }
+            Expr::Not(inner) => {
+                let val = self.expr(inner, slot)?;
+                let dest = self.temp(slot);
+                self.emit(format!("EQ {}, #0, {}", val, dest));
+                dest
+            }
+            Expr::Binary(op @ BinOp::And, lhs, rhs) | Expr::Binary(op @ BinOp::Or, lhs, rhs) => {
+                let (short, end) = (self.label(), self.label());
+                let lhs = self.expr(lhs, slot)?;
+                let jump = if *op == BinOp::And { "JF" } else { "JT" };
+                self.emit(format!("{} {}, #{}", jump, lhs, short));
+                let rhs = self.expr(rhs, slot)?;
+                let dest = self.temp(slot);
+                self.emit(format!("EQ {}, #0, {}", rhs, dest));
+                self.emit(format!("EQ {}, #0, {}", dest, dest));
+                self.emit(format!("JT #1, #{}", end));
+                self.place_label(&short);
+                let result = if *op == BinOp::And { 0 } else { 1 };
+                self.emit(format!("ADD #{}, #0, {}", result, dest));
+                self.place_label(&end);
+                dest
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let mut lhs_val = self.expr(lhs, slot)?;
+                // the right side could change a variable the left side read, so keep its value
+                if rhs.calls_function() && !lhs_val.starts_with('#') {
+                    let temp = self.temp(slot);
+                    self.copy(&lhs_val, &temp);
+                    lhs_val = temp;
+                }
+                let lhs = lhs_val;
+                let rhs = self.expr(rhs, slot + 1)?;
+                let dest = self.temp(slot);
+                let (mnemonic, a, b, negate) = match op {
+                    BinOp::Add => ("ADD", lhs, rhs, false),
+                    BinOp::Sub => {
+                        let rhs = match rhs.strip_prefix('#') {
+                            Some(num) => {
+                                format!("#{}", num.parse::<isize>().unwrap().wrapping_neg())
+                            }
+                            None => {
+                                let negated = self.temp(slot + 1);
+                                self.emit(format!("MUL {}, #-1, {}", rhs, negated));
+                                negated
+                            }
+                        };
+                        ("ADD", lhs, rhs, false)
+                    }
+                    BinOp::Mul => ("MUL", lhs, rhs, false),
+                    BinOp::Less => ("LT", lhs, rhs, false),
+                    BinOp::Greater => ("LT", rhs, lhs, false),
+                    BinOp::LessEq => ("LT", rhs, lhs, true),
+                    BinOp::GreaterEq => ("LT", lhs, rhs, true),
+                    BinOp::Equal => ("EQ", lhs, rhs, false),
+                    BinOp::NotEqual => ("EQ", lhs, rhs, true),
+                    BinOp::And | BinOp::Or => unreachable!(),
+                };
+                self.emit(format!("{} {}, {}, {}", mnemonic, a, b, dest));
+                if negate {
+                    self.emit(format!("EQ {}, #0, {}", dest, dest));
+                }
+                dest
+            }
+        };
+        Ok(dest)
+    }
+
+    /// Compile a call to a function or to `input` or `output`
+    fn call(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        line: usize,
+        slot: usize,
+    ) -> Result<String, CompileError> {
+        let params = match name {
+            "input" | "output" => (name == "output") as usize,
+            _ => match self.functions.get(name) {
+                Some(params) => *params,
+                None => return error(line, format!("unknown function '{}'", name)),
+            },
+        };
+        if args.len() != params {
+            return error(
+                line,
+                format!(
+                    "'{}' takes {} argument(s), found {}",
+                    name,
+                    params,
+                    args.len()
+                ),
+            );
+        }
+
+        // evaluate every argument before any is stored in the new frame, as evaluating one
+        // could involve another call
+        let mut values = Vec::new();
+        for (n, arg) in args.iter().enumerate() {
+            let value = self.expr(arg, slot + n)?;
+            let value = match value.starts_with('#') || name == "output" {
+                true => value,
+                false => {
+                    let temp = self.temp(slot + n);
+                    self.copy(&value, &temp);
+                    temp
+                }
+            };
+            values.push(value);
+        }
+
+        match name {
+            "input" => {
+                let dest = self.temp(slot);
+                self.emit(format!("IN {}", dest));
+                Ok(dest)
+            }
+            "output" => {
+                self.emit(format!("OUT {}", values[0]));
+                Ok("#0".to_owned())
+            }
+            _ => {
+                for (n, value) in values.iter().enumerate() {
+                    self.emit(format!("ADD {}, #0, rb+{}", value, frame(n + 1)));
+                }
+                let ret = self.label();
+                self.emit(format!("ADD #{}, #0, rb+{}", ret, frame(0)));
+                self.emit(format!("ARB #{}", frame(0)));
+                self.emit(format!("JT #1, #f_{}", name));
+                self.place_label(&ret);
+                self.emit(format!("ARB #-{}", frame(0)));
+                let dest = self.temp(slot);
+                self.emit(format!("ADD rb+{}, #0, {}", frame(1), dest));
+                Ok(dest)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{interpret, Checked, IntcodeError, Machine, Memory, State};
+    use super::*;
+
+    /// Compile `src` and run it with `input`, returning the output
+    fn run(src: &str, input: &[isize]) -> Vec<isize> {
+        let mut mem = Memory::from(compile(src).unwrap());
+        let mut output = Vec::new();
+        interpret(&mut mem, input, &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn sum_of_squares() {
+        let src = "
+            // outputs the sum of the squares of the values read, stopping at 0
+            fn square(x) {
+                return x * x;
+            }
+
+            let total = 0;
+            let n = input();
+            while n != 0 {
+                total = total + square(n);
+                n = input();
+            }
+            output(total);
+        ";
+        assert_eq!(run(src, &[1, 2, 3, 0]), vec![14]);
+        assert_eq!(run(src, &[-4, 0]), vec![16]);
+    }
+
+    #[test]
+    fn operators() {
+        let src = "
+            let a = input();
+            let b = input();
+            output(a + b);
+            output(a - b);
+            output(a - 3);
+            output(a * b);
+            output(-a);
+            output(a < b);
+            output(a <= b);
+            output(a > b);
+            output(a >= b);
+            output(a == b);
+            output(a != b);
+            output(!a);
+            output(a && b);
+            output(a || b);
+            output(1 + 2 * 3 - (4 - 1) * -2);
+        ";
+        assert_eq!(
+            run(src, &[5, 7]),
+            vec![12, -2, 2, 35, -5, 1, 1, 0, 0, 0, 1, 0, 1, 1, 13]
+        );
+        assert_eq!(
+            run(src, &[0, 0]),
+            vec![0, 0, -3, 0, 0, 0, 1, 0, 1, 1, 0, 1, 0, 0, 13]
+        );
+    }
+
+    #[test]
+    fn short_circuits() {
+        // the second input is only read when the first doesn't settle the answer
+        let src = "
+            if input() == 1 || input() == 2 {
+                output(1);
+            } else if input() == 3 && input() == 4 {
+                output(2);
+            } else {
+                output(3);
+            }
+            output(input());
+        ";
+        assert_eq!(run(src, &[1, 9]), vec![1, 9]);
+        assert_eq!(run(src, &[0, 2, 9]), vec![1, 9]);
+        assert_eq!(run(src, &[0, 0, 3, 4, 9]), vec![2, 9]);
+        assert_eq!(run(src, &[0, 0, 0, 9]), vec![3, 9]);
+    }
+
+    #[test]
+    fn functions_and_scopes() {
+        let src = "
+            let calls = 0;
+
+            fn fib(n) {
+                calls = calls + 1;
+                if n < 2 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            // remainder by repeated subtraction, there's no division
+            fn rem(a, b) {
+                while a >= b {
+                    a = a - b;
+                }
+                return a;
+            }
+
+            fn is_prime(n) {
+                let d = 2;
+                while d * d <= n {
+                    if rem(n, d) == 0 {
+                        return 0;
+                    }
+                    d = d + 1;
+                }
+                return n > 1;
+            }
+
+            fn nothing() {
+                let x = 5;
+            }
+
+            output(fib(input()));
+            output(calls);
+            let n = 0;
+            while n < 30 {
+                if is_prime(n) {
+                    output(n);
+                }
+                n = n + 1;
+            }
+            // arguments are all evaluated before the call
+            output(rem(fib(7), rem(17, 10)));
+            output(nothing());
+
+            fn shadow(x) {
+                if x {
+                    let x = 2;
+                    output(x);
+                }
+                return x;
+            }
+            output(shadow(1));
+        ";
+        assert_eq!(
+            run(src, &[10]),
+            vec![55, 177, 2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 6, 0, 2, 1]
+        );
+    }
+
+    #[test]
+    fn left_operand_read_first() {
+        // the call changes the global after the left side has been read
+        let src = "
+            let g = 1;
+            fn f() {
+                g = 100;
+                return 0;
+            }
+            output(g + f());
+            output(g);
+        ";
+        assert_eq!(run(src, &[]), vec![1, 100]);
+    }
+
+    #[test]
+    fn deep_recursion_with_other_words() {
+        let src = "
+            fn factorial(n) {
+                if n == 0 {
+                    return 1;
+                }
+                return n * factorial(n - 1);
+            }
+            output(factorial(input()));
+        ";
+        let program = compile(src).unwrap();
+
+        // 25! needs more than 64 bits
+        let mem: Memory<i128> = program.iter().map(|word| *word as i128).collect();
+        let mut machine = Machine::from_memory(mem);
+        machine.push_input(25);
+        assert_eq!(machine.run(), Ok(State::Output(15511210043330985984000000)));
+        let mem: Memory<Checked<i64>> = program.iter().map(|word| Checked(*word as i64)).collect();
+        let mut machine = Machine::from_memory(mem);
+        machine.push_input(Checked(25));
+        assert!(matches!(machine.run(), Err(IntcodeError::Overflow { .. })));
+    }
+
+    #[test]
+    fn emits_assembly() {
+        let asm = compile_to_asm("fn f(a) { return a + 1; }\nlet x = f(2);\noutput(x);").unwrap();
+        assert_eq!(
+            asm,
+            "        ARB #stack
+        ADD #2, #0, rb+3
+        ADD #L1, #0, rb+2
+        ARB #2
+        JT #1, #f_f
+        L1:
+        ARB #-2
+        ADD rb+3, #0, rb+1
+        ADD rb+1, #0, [g_x]
+        OUT [g_x]
+        HALT
+f_f:
+        ADD rb+1, #1, rb+2
+        ADD rb+2, #0, rb+1
+        JT #1, rb+0
+        ADD #0, #0, rb+1
+        JT #1, rb+0
+g_x: .data 0
+stack: .data 0
+"
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let err = |src: &str| compile(src).unwrap_err().to_string();
+        assert_eq!(
+            err("let x = 1;\noutput(y);"),
+            "line 2: unknown variable 'y'"
+        );
+        assert_eq!(
+            err("let x = 1\noutput(x);"),
+            "line 2: expected ';', found 'output'"
+        );
+        assert_eq!(
+            err("fn f(a) {}\nf(1, 2);"),
+            "line 2: 'f' takes 1 argument(s), found 2"
+        );
+        assert_eq!(err("g();"), "line 1: unknown function 'g'");
+        assert_eq!(err("return 1;"), "line 1: return outside a function");
+        assert_eq!(
+            err("fn f() {}\nfn f() {}"),
+            "line 2: function 'f' defined more than once"
+        );
+        assert_eq!(
+            err("fn f(a, a) {}"),
+            "line 1: parameter 'a' appears more than once"
+        );
+        assert_eq!(
+            err("let while = 1;"),
+            "line 1: expected a name, found 'while'"
+        );
+        assert_eq!(
+            err("output(1 + );"),
+            "line 1: expected an expression, found ')'"
+        );
+        assert_eq!(err("if 1 {\n"), "line 1: expected '}', found end of input");
+        assert_eq!(err("let x = 1 % 2;"), "line 1: unexpected character '%'");
+        assert_eq!(
+            err("fn f() { fn g() {} }"),
+            "line 1: functions can only be defined at the top level"
+        );
+        let function_local = "fn f() { let y = 1; }\noutput(y);";
+        assert_eq!(err(function_local), "line 2: unknown variable 'y'");
+    }
+}
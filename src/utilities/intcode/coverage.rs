@@ -0,0 +1,226 @@
+//! Record of which addresses a `Machine` with coverage turned on used as code and as data.
+//!
+//! Every executed instruction marks the words it was decoded from as code, and the cells its
+//! position and relative mode parameters point at as read or written. The annotated listing built
+//! from this shows which parts of a program never ran, e.g. the self-test branches of the day 5
+//! diagnostic that an input doesn't reach.
+//!
+//! ```text
+//! exec           2: ADD [225], [6], [6]            ; 1,225,6,6
+//! exec  rw       6: JT #1, #238                    ; 1105,1,238
+//!                9: DATA 225                       ; 225
+//! never         10: OUT #0                         ; 104,0
+//! data  rw     225: DATA 20                        ; 20
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::disasm::{self, Line};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    // length of each executed instruction by its address
+    instructions: BTreeMap<usize, usize>,
+    reads: BTreeSet<usize>,
+    writes: BTreeSet<usize>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note one executed instruction and the cells its parameters read and wrote
+    pub(super) fn record(&mut self, ip: usize, len: usize, reads: &[usize], write: Option<usize>) {
+        self.instructions.insert(ip, len);
+        self.reads.extend(reads);
+        self.writes.extend(write);
+    }
+
+    /// Add the addresses covered by `other` into this coverage
+    pub fn merge(&mut self, other: &Coverage) {
+        self.instructions.extend(other.instructions.iter());
+        self.reads.extend(other.reads.iter());
+        self.writes.extend(other.writes.iter());
+    }
+
+    /// Whether an instruction starting at `addr` was executed
+    pub fn executed(&self, addr: usize) -> bool {
+        self.instructions.contains_key(&addr)
+    }
+
+    /// Whether `addr` is part of any executed instruction, as opcode or parameter
+    pub fn is_code(&self, addr: usize) -> bool {
+        // no instruction is longer than 4 words
+        self.instructions
+            .range(addr.saturating_sub(3)..=addr)
+            .any(|(start, len)| addr < start + len)
+    }
+
+    /// Whether an instruction read the value at `addr` through a parameter
+    pub fn was_read(&self, addr: usize) -> bool {
+        self.reads.contains(&addr)
+    }
+
+    /// Whether an instruction wrote to `addr`
+    pub fn was_written(&self, addr: usize) -> bool {
+        self.writes.contains(&addr)
+    }
+
+    /// Addresses read or written which were never executed as part of an instruction
+    pub fn data(&self) -> Vec<usize> {
+        self.reads
+            .union(&self.writes)
+            .copied()
+            .filter(|addr| !self.is_code(*addr))
+            .collect()
+    }
+
+    /// Addresses of the instructions in `mem` which never ran, as found by `annotate`
+    pub fn unexecuted(&self, mem: &[isize]) -> Vec<usize> {
+        self.lines(mem)
+            .into_iter()
+            .filter(|(_, never)| *never)
+            .map(|(line, _)| line.addr)
+            .collect()
+    }
+
+    /// Disassembly of `mem` marking executed instructions, data read or written, and code which
+    /// was never executed.
+    ///
+    /// Executed instructions are decoded where they were run from, so code reached part way
+    /// through what a plain listing would show lines up correctly. `mem` is usually memory as the
+    /// program left it, an instruction overwritten after it ran is marked as changed.
+    pub fn annotate(&self, mem: &[isize]) -> String {
+        self.lines(mem)
+            .iter()
+            .map(|(line, never)| {
+                let words = line.addr..line.addr + line.words.len();
+                let kind = match *never {
+                    true => "never",
+                    false if self.executed(line.addr) => "exec",
+                    false if words.clone().any(|addr| self.is_data(addr)) => "data",
+                    false => "",
+                };
+                let read = if words.clone().any(|addr| self.was_read(addr)) {
+                    "r"
+                } else {
+                    " "
+                };
+                let written = if words.clone().any(|addr| self.was_written(addr)) {
+                    "w"
+                } else {
+                    " "
+                };
+                format!("{:<5} {}{}  {}\n", kind, read, written, line)
+            })
+            .collect()
+    }
+
+    fn is_data(&self, addr: usize) -> bool {
+        (self.was_read(addr) || self.was_written(addr)) && !self.is_code(addr)
+    }
+
+    /// Listing lines, each flagged when it is an instruction which never ran
+    fn lines(&self, mem: &[isize]) -> Vec<(Line, bool)> {
+        let mut lines = Vec::new();
+        let mut addr = 0;
+        while addr < mem.len() {
+            let decoded = match disasm::decode(mem, addr) {
+                Some((text, len)) if self.executed(addr) => Some((text, len, false)),
+                // overwritten since it ran, keep the words it ran from together
+                None if self.executed(addr) => {
+                    let len = self.instructions[&addr].min(mem.len() - addr);
+                    Some((format!("DATA {} (changed)", mem[addr]), len, false))
+                }
+                // only count as unexecuted code if none of it was used some other way
+                Some((text, len)) if (addr..addr + len).all(|addr| self.untouched(addr)) => {
+                    Some((text, len, true))
+                }
+                _ => None,
+            };
+            let (text, len, never) =
+                decoded.unwrap_or_else(|| (format!("DATA {}", mem[addr]), 1, false));
+            lines.push((
+                Line {
+                    addr,
+                    words: mem[addr..addr + len].to_vec(),
+                    text,
+                },
+                never,
+            ));
+            addr += len;
+        }
+        lines
+    }
+
+    fn untouched(&self, addr: usize) -> bool {
+        !self.is_code(addr) && !self.was_read(addr) && !self.was_written(addr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{day05, Machine, State};
+    use super::*;
+
+    #[test]
+    fn marks_code_and_data() {
+        // counts down from 3 then outputs and halts, with an output at 10 which can't be reached
+        let program = vec![1001, 12, -1, 12, 1005, 12, 0, 104, 7, 99, 4, 12, 3];
+        let mut machine = Machine::new(program.clone());
+        machine.set_coverage(true);
+        while machine.run().unwrap() != State::Halted {}
+
+        let coverage = machine.coverage().unwrap();
+        assert!(coverage.executed(4) && coverage.executed(9));
+        assert!(!coverage.executed(5) && coverage.is_code(5));
+        assert!(coverage.was_read(12) && coverage.was_written(12));
+        assert!(!coverage.was_read(7));
+        assert_eq!(coverage.data(), vec![12]);
+        assert_eq!(coverage.unexecuted(&program), vec![10]);
+
+        let annotated = coverage.annotate(machine.memory().as_slice());
+        let lines: Vec<&str> = annotated.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("exec           0: ADD [12], #-1, [12]"));
+        assert!(lines[4].starts_with("never         10: OUT [12]"));
+        assert!(lines[5].starts_with("data  rw      12: DATA 0"));
+    }
+
+    #[test]
+    fn diagnostic_self_tests() {
+        let program = day05();
+        let mut total = Coverage::new();
+        let mut runs = Vec::new();
+        for input in [1, 5].iter() {
+            let mut machine = Machine::new(program.clone());
+            machine.set_coverage(true);
+            machine.push_input(*input);
+            while machine.run().unwrap() != State::Halted {}
+            total.merge(machine.coverage().unwrap());
+            runs.push(machine);
+        }
+
+        // part 2 jumps straight past the part 1 tests, then writes over the first instruction
+        let part2 = runs[1].coverage().unwrap();
+        assert!(!part2.executed(10));
+        let annotated = part2.annotate(runs[1].memory().as_slice());
+        assert!(
+            annotated.starts_with("exec  rw       0: DATA 314 (changed)             ; 314,225\n")
+        );
+
+        // together the two runs reach everything but the branches taken when a test fails
+        let unexecuted = total.unexecuted(program.as_slice());
+        assert_eq!(unexecuted.len(), 20);
+        for addr in unexecuted {
+            let (text, _) = disasm::decode(program.as_slice(), addr).unwrap();
+            assert!(
+                text == "JT #1, #99999" || text.ends_with(", [223]"),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(total.data(), vec![223, 224, 225, 226, 227, 677]);
+    }
+}
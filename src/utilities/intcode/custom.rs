@@ -0,0 +1,361 @@
+//! Extra opcodes run by a `Machine`, for trying out instruction set extensions.
+//!
+//! Each opcode is registered with a mnemonic, the role of each of its parameters and a closure
+//! doing the work. Instruction words encode addressing modes as usual, and the machine resolves
+//! parameters, applies limits and keeps profiles, coverage and history just as for the built in
+//! instructions. A division instruction could be added as
+//!
+//! ```text
+//! registry.register(10, "DIV", &[Role::Read, Role::Read, Role::Write], |call| {
+//!     if call.arg(2) == 0 {
+//!         return Err("division by zero".to_owned());
+//!     }
+//!     call.set(3, call.arg(1) / call.arg(2));
+//!     Ok(())
+//! });
+//! ```
+//!
+//! so that `10, 20, 21, 22` stores `[20] / [21]` in `[22]`. Other tools, such as the disassembler,
+//! only know the built in instructions.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+use super::OpCode;
+
+/// How an instruction uses one of its parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Value is read, in any addressing mode
+    Read,
+    /// Result is written, immediate mode is an error
+    Write,
+    /// Value is read and a result written back, e.g. a counter
+    ReadWrite,
+}
+
+impl Role {
+    fn reads(self) -> bool {
+        self != Role::Write
+    }
+
+    fn writes(self) -> bool {
+        self != Role::Read
+    }
+}
+
+type Exec<W> = dyn Fn(&mut Call<W>) -> Result<(), String> + Send + Sync;
+
+/// A registered opcode
+pub(super) struct Custom<W> {
+    pub(super) mnemonic: &'static str,
+    pub(super) roles: Vec<Role>,
+    exec: Box<Exec<W>>,
+}
+
+impl<W> Custom<W> {
+    /// Parameter written by the instruction, counting from 1
+    pub(super) fn write_param(&self) -> Option<usize> {
+        self.roles
+            .iter()
+            .position(|role| role.writes())
+            .map(|n| n + 1)
+    }
+
+    pub(super) fn exec(&self, call: &mut Call<W>) -> Result<(), String> {
+        (self.exec)(call)
+    }
+}
+
+/// Opcodes added to a machine with `Machine::set_opcodes`, cheap to clone
+pub struct Registry<W = isize> {
+    opcodes: BTreeMap<u8, Arc<Custom<W>>>,
+}
+
+impl<W> Registry<W> {
+    pub fn new() -> Self {
+        Registry {
+            opcodes: BTreeMap::new(),
+        }
+    }
+
+    /// Add opcode `code`, taking one parameter per entry in `roles`.
+    ///
+    /// `exec` runs the instruction, any error it returns stops the machine with
+    /// `IntcodeError::Custom`. Panics if `code` isn't a free opcode from 1 to 98, or if the
+    /// instruction has more than three parameters or writes more than one of them.
+    pub fn register(
+        &mut self,
+        code: u8,
+        mnemonic: &'static str,
+        roles: &[Role],
+        exec: impl Fn(&mut Call<W>) -> Result<(), String> + Send + Sync + 'static,
+    ) {
+        assert!(
+            (1..99).contains(&code) && OpCode::try_from(code as isize).is_err(),
+            "Opcode {} is built in or out of range",
+            code
+        );
+        assert!(
+            !self.opcodes.contains_key(&code),
+            "Opcode {} is already registered",
+            code
+        );
+        assert!(roles.len() <= 3, "Instructions take at most 3 parameters");
+        assert!(
+            roles.iter().filter(|role| role.writes()).count() <= 1,
+            "Instructions write at most one parameter"
+        );
+        let custom = Custom {
+            mnemonic,
+            roles: roles.to_vec(),
+            exec: Box::new(exec),
+        };
+        self.opcodes.insert(code, Arc::new(custom));
+    }
+
+    /// The opcode instruction `word` would run, if it is a registered one
+    pub(super) fn lookup(&self, word: i128) -> Option<Arc<Custom<W>>> {
+        if self.opcodes.is_empty() || word <= 0 {
+            return None;
+        }
+        self.opcodes.get(&((word % 100) as u8)).cloned()
+    }
+}
+
+impl<W> Default for Registry<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W> Clone for Registry<W> {
+    fn clone(&self) -> Self {
+        Registry {
+            opcodes: self.opcodes.clone(),
+        }
+    }
+}
+
+impl<W> fmt::Debug for Registry<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.opcodes
+                    .iter()
+                    .map(|(code, custom)| (code, custom.mnemonic)),
+            )
+            .finish()
+    }
+}
+
+/// A custom instruction being executed, handed to its closure.
+///
+/// Parameters count from 1, as in the instruction word.
+#[derive(Debug)]
+pub struct Call<W> {
+    relative_base: W,
+    args: [Option<W>; 3],
+    roles: [Option<Role>; 3],
+    pub(super) result: Option<W>,
+    pub(super) jump: Option<W>,
+    pub(super) output: Option<W>,
+}
+
+impl<W: Copy> Call<W> {
+    pub(super) fn new(relative_base: W, roles: &[Role], args: [Option<W>; 3]) -> Self {
+        let mut call_roles = [None; 3];
+        for (slot, role) in call_roles.iter_mut().zip(roles) {
+            *slot = Some(*role);
+        }
+        Call {
+            relative_base,
+            args,
+            roles: call_roles,
+            result: None,
+            jump: None,
+            output: None,
+        }
+    }
+
+    /// Value of parameter `n`, which must be read by the instruction
+    pub fn arg(&self, n: usize) -> W {
+        match self.roles.get(n.wrapping_sub(1)) {
+            Some(Some(role)) if role.reads() => self.args[n - 1].unwrap(),
+            _ => panic!("Parameter {} isn't read by the instruction", n),
+        }
+    }
+
+    /// Write `val` through parameter `n`, which must be written by the instruction. Nothing is
+    /// written unless this is called.
+    pub fn set(&mut self, n: usize, val: W) {
+        match self.roles.get(n.wrapping_sub(1)) {
+            Some(Some(role)) if role.writes() => self.result = Some(val),
+            _ => panic!("Parameter {} isn't written by the instruction", n),
+        }
+    }
+
+    /// Continue from `addr` rather than the next instruction
+    pub fn jump(&mut self, addr: W) {
+        self.jump = Some(addr);
+    }
+
+    /// Produce `val` as output, as an output instruction would
+    pub fn output(&mut self, val: W) {
+        self.output = Some(val);
+    }
+
+    pub fn relative_base(&self) -> W {
+        self.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, val: W) {
+        self.relative_base = val;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{IntcodeError, Machine, State};
+    use super::*;
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(10, "DIV", &[Role::Read, Role::Read, Role::Write], |call| {
+            if call.arg(2) == 0 {
+                return Err("division by zero".to_owned());
+            }
+            call.set(3, call.arg(1) / call.arg(2));
+            Ok(())
+        });
+        // decrement a counter, jumping while it is still positive
+        registry.register(20, "DJNZ", &[Role::ReadWrite, Role::Read], |call| {
+            let count = call.arg(1) - 1;
+            call.set(1, count);
+            if count > 0 {
+                call.jump(call.arg(2));
+            }
+            Ok(())
+        });
+        // output a value and move the relative base past it
+        registry.register(30, "PUSH", &[Role::Read], |call| {
+            call.output(call.arg(1));
+            call.set_relative_base(call.relative_base() + 1);
+            Ok(())
+        });
+        registry
+    }
+
+    #[test]
+    fn runs_custom_opcodes() {
+        // divides 100 by 7, then counts down from 3 outputting the counter, then pushes 8
+        let program = vec![
+            10, 15, 16, 17, 4, 17, 4, 14, 1020, 14, 6, 130, 8, 99, 3, 100, 7, 0,
+        ];
+        let mut machine = Machine::new(program.clone());
+        machine.set_opcodes(registry());
+        machine.set_profiling(true);
+        machine.set_coverage(true);
+        machine.set_history(true);
+        let mut output = Vec::new();
+        assert_eq!(machine.run_with((), &mut output), Ok(State::Halted));
+        assert_eq!(output, vec![14, 3, 2, 1, 8]);
+        assert_eq!(machine.memory()[14], 0);
+        assert_eq!(machine.relative_base(), 1);
+
+        let profile = machine.profile().unwrap();
+        assert_eq!(
+            profile.opcode_counts(),
+            vec![("OUT", 4), ("DJNZ", 3), ("DIV", 1), ("PUSH", 1)]
+        );
+        assert_eq!(profile.outputs(), 5);
+        let coverage = machine.coverage().unwrap();
+        assert!(coverage.executed(8) && coverage.is_code(10));
+        assert!(coverage.was_read(14) && coverage.was_written(14));
+        assert!(coverage.was_written(17));
+
+        // custom instructions can be stepped back like any other
+        while machine.steps() > 1 {
+            machine.step_back().unwrap();
+        }
+        assert_eq!(machine.memory()[14], 3);
+        assert_eq!(machine.relative_base(), 0);
+        assert_eq!(machine.step_back().unwrap().write.unwrap().new, 14);
+
+        // without the registry the first word is just an invalid opcode
+        let mut plain = Machine::new(program);
+        assert_eq!(
+            plain.run(),
+            Err(IntcodeError::InvalidOpcode { ip: 0, word: 10 })
+        );
+    }
+
+    #[test]
+    fn custom_errors() {
+        let mut machine = Machine::new(vec![1010, 5, 0, 7, 99, 1]);
+        machine.set_opcodes(registry());
+        // clones share the registry
+        let mut copy = machine.clone();
+        let err = machine.run().unwrap_err();
+        assert_eq!(copy.run(), Err(err.clone()));
+        assert_eq!(
+            err,
+            IntcodeError::Custom {
+                ip: 0,
+                word: 1010,
+                message: "division by zero".to_owned()
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "division by zero (ip: 0, instruction: 1010)"
+        );
+
+        let mut machine = Machine::new(vec![11110, 1, 2, 3, 99]);
+        machine.set_opcodes(registry());
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::ImmediateWrite {
+                ip: 0,
+                word: 11110,
+                param: 3
+            })
+        );
+        let mut machine = Machine::new(vec![330, 1, 99]);
+        machine.set_opcodes(registry());
+        assert!(matches!(
+            machine.run(),
+            Err(IntcodeError::InvalidMode { param: 1, .. })
+        ));
+
+        // a failing instruction changes nothing, even when its write would have been fine
+        let mut registry = registry();
+        registry.register(40, "SETJ", &[Role::Write], |call| {
+            call.set(1, 42);
+            call.jump(-5);
+            Ok(())
+        });
+        let mut machine = Machine::new(vec![40, 5, 99, 0, 0, 0]);
+        machine.set_opcodes(registry);
+        machine.set_history(true);
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::NegativeAddress {
+                ip: 0,
+                word: 40,
+                addr: -5
+            })
+        );
+        assert_eq!(machine.memory()[5], 0);
+        assert_eq!((machine.ip(), machine.steps()), (0, 0));
+        assert!(machine.history().unwrap().records().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "built in")]
+    fn refuses_built_in_opcodes() {
+        registry().register(7, "LESS", &[Role::Read], |_| Ok(()));
+    }
+}
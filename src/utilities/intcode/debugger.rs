@@ -0,0 +1,424 @@
+//! Step debugger for IntCode programs.
+//!
+//! `Debugger` wraps a `Machine` and drives it one instruction at a time, stopping at breakpoints
+//! (by instruction address) and watchpoints (when a memory cell changes value). The machine keeps
+//! a history of what it executes, so the debugger can also step backwards. It can be used
+//! directly from code, or through `Debugger::execute` which understands short text commands:
+//!
+//! ```text
+//! s [n]          step n instructions (default 1)
+//! sb [n]         step back n instructions (default 1)
+//! rw <step>      rewind to an earlier step count
+//! lw <addr>      show which instruction last wrote to addr
+//! c              continue until a breakpoint, watchpoint, input request or halt
+//! b <addr>       set breakpoint          db <addr>   delete breakpoint
+//! w <addr>       set watchpoint          dw <addr>   delete watchpoint
+//! i <v>[,<v>..]  queue input values
+//! r              show registers
+//! m <addr> [n]   show n memory cells starting at addr (default 8, at most 1000)
+//! l [n]          list n instructions starting at ip (default 5)
+//! o              show output produced so far
+//! ```
+
+use std::collections::BTreeSet;
+
+use super::{disasm, IntcodeError, Machine, State};
+
+/// Most memory cells the `m` command will show at once
+const MAX_CELLS: usize = 1000;
+
+/// Why the debugger handed control back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// Requested number of instructions were executed
+    Stepped,
+    /// About to execute the instruction at a breakpoint
+    Breakpoint(usize),
+    /// A watched cell changed value
+    Watchpoint {
+        addr: usize,
+        old: isize,
+        new: isize,
+    },
+    /// Program wants input and none is queued
+    NeedsInput,
+    Halted,
+}
+
+pub struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    output: Vec<isize>,
+}
+
+impl Debugger {
+    /// Debug `machine`, turning on its history
+    pub fn new(mut machine: Machine) -> Self {
+        machine.set_history(true);
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn push_input(&mut self, val: isize) {
+        self.machine.push_input(val);
+    }
+
+    /// Every value the program has written so far
+    pub fn output(&self) -> &[isize] {
+        &self.output
+    }
+
+    /// Number of instructions executed so far
+    pub fn steps(&self) -> usize {
+        self.machine.steps()
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn add_watchpoint(&mut self, addr: usize) {
+        self.watchpoints.insert(addr);
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr)
+    }
+
+    /// Execute a single instruction, ignoring breakpoints
+    pub fn step(&mut self) -> Result<Stop, IntcodeError> {
+        let watched: Vec<(usize, isize)> = self
+            .watchpoints
+            .iter()
+            .map(|addr| (*addr, self.machine.memory()[*addr]))
+            .collect();
+
+        match self.machine.step()? {
+            Some(State::NeedsInput) => return Ok(Stop::NeedsInput),
+            Some(State::Halted) => return Ok(Stop::Halted),
+            Some(State::Output(val)) => self.output.push(val),
+            None => (),
+        }
+
+        for (addr, old) in watched {
+            let new = self.machine.memory()[addr];
+            if new != old {
+                return Ok(Stop::Watchpoint { addr, old, new });
+            }
+        }
+        Ok(Stop::Stepped)
+    }
+
+    /// Undo the last instruction executed, returns false if there is nothing to undo
+    pub fn step_back(&mut self) -> bool {
+        match self.machine.step_back() {
+            Some(record) => {
+                if record.output.is_some() {
+                    self.output.pop();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Step back until `steps()` is `step`, returns false if that is out of reach
+    pub fn rewind(&mut self, step: usize) -> bool {
+        let undone = self.machine.history().map_or(0, |history| {
+            history
+                .records()
+                .iter()
+                .filter(|record| record.step >= step && record.output.is_some())
+                .count()
+        });
+        if !self.machine.rewind(step) {
+            return false;
+        }
+        self.output.truncate(self.output.len() - undone);
+        true
+    }
+
+    /// Run until a breakpoint or watchpoint is hit, input is needed or the program halts.
+    ///
+    /// The current instruction is always executed, so continuing from a breakpoint makes progress.
+    pub fn cont(&mut self) -> Result<Stop, IntcodeError> {
+        loop {
+            match self.step()? {
+                Stop::Stepped => (),
+                stop => return Ok(stop),
+            }
+            let ip = self.machine.ip();
+            if self.breakpoints.contains(&ip) {
+                return Ok(Stop::Breakpoint(ip));
+            }
+        }
+    }
+
+    /// Run a single debugger command, returning the text to show the user
+    pub fn execute(&mut self, command: &str) -> String {
+        let mut parts = command.split_whitespace();
+        let name = parts.next().unwrap_or("");
+        let args: Vec<&str> = parts.collect();
+        let arg = |n: usize| -> Result<Option<usize>, String> {
+            match args.get(n) {
+                Some(text) => text
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("invalid number '{}'", text)),
+                None => Ok(None),
+            }
+        };
+        let required = |n: usize| -> Result<usize, String> {
+            arg(n)?.ok_or_else(|| format!("'{}' needs an address", name))
+        };
+
+        let result = match name {
+            "s" => arg(0).map(|count| {
+                let mut stop = Ok(Stop::Stepped);
+                for _ in 0..count.unwrap_or(1) {
+                    stop = self.step();
+                    if stop != Ok(Stop::Stepped) {
+                        break;
+                    }
+                }
+                self.describe(stop)
+            }),
+            "sb" => arg(0).map(|count| {
+                let count = count.unwrap_or(1);
+                let undone = (0..count).take_while(|_| self.step_back()).count();
+                let reason = match undone == count {
+                    true => "stepped back".to_owned(),
+                    false => format!("stepped back {}, no earlier history", undone),
+                };
+                format!("{}\n{}\n{}", reason, self.registers(), self.list(1))
+            }),
+            "rw" => required(0).and_then(|step| match self.rewind(step) {
+                true => Ok(format!(
+                    "rewound to step {}\n{}\n{}",
+                    step,
+                    self.registers(),
+                    self.list(1)
+                )),
+                false => Err(format!("step {} is not in the history", step)),
+            }),
+            "lw" => required(0).map(|addr| {
+                let history = self.machine.history().unwrap();
+                match history.last_write(addr) {
+                    Some(record) => {
+                        let write = record.write.unwrap();
+                        format!(
+                            "{} last written at step {} by the instruction at {} ({} -> {})",
+                            addr, record.step, record.ip, write.old, write.new
+                        )
+                    }
+                    None => format!("{} not written since history began", addr),
+                }
+            }),
+            "c" => Ok({
+                let stop = self.cont();
+                self.describe(stop)
+            }),
+            "b" => required(0).map(|addr| {
+                self.add_breakpoint(addr);
+                format!("breakpoint set at {}", addr)
+            }),
+            "db" => required(0).map(|addr| match self.remove_breakpoint(addr) {
+                true => format!("breakpoint at {} removed", addr),
+                false => format!("no breakpoint at {}", addr),
+            }),
+            "w" => required(0).map(|addr| {
+                self.add_watchpoint(addr);
+                format!("watching {}", addr)
+            }),
+            "dw" => required(0).map(|addr| match self.remove_watchpoint(addr) {
+                true => format!("watchpoint at {} removed", addr),
+                false => format!("no watchpoint at {}", addr),
+            }),
+            "i" => args
+                .join("")
+                .split(',')
+                .filter(|val| !val.is_empty())
+                .map(|val| val.parse().map_err(|_| format!("invalid input '{}'", val)))
+                .collect::<Result<Vec<isize>, String>>()
+                .map(|vals| {
+                    for val in vals.iter() {
+                        self.push_input(*val);
+                    }
+                    format!("queued {} input value(s)", vals.len())
+                }),
+            "r" => Ok(self.registers()),
+            "m" => required(0).and_then(|addr| {
+                let count = arg(1)?.unwrap_or(8);
+                if count > MAX_CELLS {
+                    return Err(format!("can show at most {} cells", MAX_CELLS));
+                }
+                let end = addr
+                    .checked_add(count)
+                    .ok_or_else(|| format!("address {} is too large", addr))?;
+                let mem = self.machine.memory();
+                let cells: Vec<String> = (addr..end)
+                    .map(|a| format!("{:>6}: {}", a, mem[a]))
+                    .collect();
+                Ok(cells.join("\n"))
+            }),
+            "l" => arg(0).map(|count| self.list(count.unwrap_or(5))),
+            "o" => Ok(format!("{:?}", self.output)),
+            _ => Err(format!("unknown command '{}'", command.trim())),
+        };
+        result.unwrap_or_else(|err| format!("error: {}", err))
+    }
+
+    fn registers(&self) -> String {
+        format!(
+            "ip: {}  rb: {}  steps: {}",
+            self.machine.ip(),
+            self.machine.relative_base(),
+            self.machine.steps()
+        )
+    }
+
+    /// Disassemble `count` instructions starting at the instruction pointer
+    fn list(&self, count: usize) -> String {
+        let mem = self.machine.memory().as_slice();
+        let mut addr = self.machine.ip();
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            if addr >= mem.len() {
+                break;
+            }
+            let (text, len) =
+                disasm::decode(mem, addr).unwrap_or_else(|| (format!("DATA {}", mem[addr]), 1));
+            let marker = if self.breakpoints.contains(&addr) {
+                '*'
+            } else {
+                ' '
+            };
+            lines.push(format!("{}{:>6}: {}", marker, addr, text));
+            addr += len;
+        }
+        lines.join("\n")
+    }
+
+    fn describe(&self, stop: Result<Stop, IntcodeError>) -> String {
+        let reason = match stop {
+            Ok(Stop::Stepped) => "stepped".to_owned(),
+            Ok(Stop::Breakpoint(addr)) => format!("hit breakpoint at {}", addr),
+            Ok(Stop::Watchpoint { addr, old, new }) => {
+                format!("watchpoint {} changed {} -> {}", addr, old, new)
+            }
+            Ok(Stop::NeedsInput) => "waiting for input".to_owned(),
+            Ok(Stop::Halted) => "halted".to_owned(),
+            Err(err) => format!("error: {}", err),
+        };
+        format!("{}\n{}\n{}", reason, self.registers(), self.list(1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn jump_prog() -> Machine {
+        // outputs 0 if the input was 0, 1 otherwise
+        Machine::new(vec![
+            3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9,
+        ])
+    }
+
+    #[test]
+    fn breakpoints_and_stepping() {
+        let mut dbg = Debugger::new(jump_prog());
+        dbg.add_breakpoint(9);
+        assert_eq!(dbg.cont(), Ok(Stop::NeedsInput));
+        dbg.push_input(5);
+        assert_eq!(dbg.step(), Ok(Stop::Stepped));
+        assert_eq!(dbg.machine().ip(), 2);
+        assert_eq!(dbg.cont(), Ok(Stop::Breakpoint(9)));
+        assert_eq!(dbg.step(), Ok(Stop::Stepped));
+        assert_eq!(dbg.output(), &[1]);
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+        assert_eq!(dbg.steps(), 4);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut dbg = Debugger::new(jump_prog());
+        dbg.add_watchpoint(12);
+        dbg.push_input(7);
+        assert_eq!(
+            dbg.cont(),
+            Ok(Stop::Watchpoint {
+                addr: 12,
+                old: -1,
+                new: 7
+            })
+        );
+        assert_eq!(dbg.machine().ip(), 2);
+    }
+
+    #[test]
+    fn stepping_back() {
+        let mut dbg = Debugger::new(jump_prog());
+        dbg.push_input(5);
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+        assert_eq!(dbg.output(), &[1]);
+        assert!(dbg.step_back());
+        assert_eq!(dbg.output(), &[] as &[isize]);
+        assert_eq!(dbg.machine().ip(), 9);
+        assert!(!dbg.rewind(4));
+        assert!(dbg.rewind(0));
+        assert!(!dbg.step_back());
+        assert_eq!(dbg.machine().memory()[12], -1);
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+        assert_eq!(dbg.output(), &[1]);
+
+        assert_eq!(
+            dbg.execute("lw 12"),
+            "12 last written at step 0 by the instruction at 0 (-1 -> 5)"
+        );
+        assert_eq!(dbg.execute("lw 3"), "3 not written since history began");
+        assert!(dbg
+            .execute("rw 1")
+            .starts_with("rewound to step 1\nip: 2  rb: 0  steps: 1"));
+        assert_eq!(dbg.execute("rw 3"), "error: step 3 is not in the history");
+        assert!(dbg
+            .execute("sb 2")
+            .starts_with("stepped back 1, no earlier history\nip: 0"));
+    }
+
+    #[test]
+    fn text_commands() {
+        let mut dbg = Debugger::new(jump_prog());
+        assert_eq!(dbg.execute("b 9"), "breakpoint set at 9");
+        assert_eq!(dbg.execute("i 0"), "queued 1 input value(s)");
+        assert!(dbg.execute("c").starts_with("hit breakpoint at 9"));
+        assert_eq!(dbg.execute("r"), "ip: 9  rb: 0  steps: 2");
+        assert_eq!(dbg.execute("m 12 2"), "    12: 0\n    13: 0");
+        assert_eq!(dbg.execute("l 2"), "*     9: OUT [13]\n     11: HALT");
+        assert!(dbg.execute("s 5").starts_with("halted"));
+        assert_eq!(dbg.execute("o"), "[0]");
+        assert_eq!(dbg.execute("b x"), "error: invalid number 'x'");
+        assert_eq!(
+            dbg.execute(&format!("m {}", usize::MAX)),
+            format!("error: address {} is too large", usize::MAX)
+        );
+        assert_eq!(
+            dbg.execute("m 0 100000"),
+            "error: can show at most 1000 cells"
+        );
+        assert_eq!(dbg.execute("zap"), "error: unknown command 'zap'");
+    }
+}
@@ -0,0 +1,263 @@
+//! Memory-mapped devices, which turn reads and writes at a range of addresses into calls.
+//!
+//! A device attached to a `Machine` with `Machine::attach` takes over its addresses: a parameter
+//! reading one of them gets its value from the device, and an instruction writing one hands the
+//! value to the device instead of storing it. Instructions themselves are always fetched from
+//! plain memory. Without any devices attached a machine behaves exactly as before.
+//!
+//! Devices have side effects, so `Machine::step_back` doesn't undo their reads and writes, and
+//! they aren't part of a `Snapshot`. Any access counts as I/O for loop detection, so polling a
+//! device isn't mistaken for an infinite loop.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Instant;
+
+use super::Word;
+
+/// Something which can be mapped into a machine's address space.
+///
+/// `offset` counts from the start of the address range the device is attached at. Implement this
+/// on a `Clone` type, the machine clones its devices along with itself.
+pub trait Device<W = isize>: DeviceClone<W> + fmt::Debug + Send {
+    /// Value seen by a parameter reading `offset`
+    fn read(&mut self, offset: usize) -> W;
+
+    /// Handle an instruction writing `val` to `offset`
+    fn write(&mut self, offset: usize, val: W);
+}
+
+/// Object safe cloning and downcasting, implemented for every `Clone` device
+pub trait DeviceClone<W> {
+    fn clone_box(&self) -> Box<dyn Device<W>>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<W, D: Device<W> + Clone + 'static> DeviceClone<W> for D {
+    fn clone_box(&self) -> Box<dyn Device<W>> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl<W> Clone for Box<dyn Device<W>> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Milliseconds of wall clock time, at offset 0.
+///
+/// Reading gives the time since the clock was made, writing sets the current reading.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    start: Instant,
+    base: i128,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Clock {
+            start: Instant::now(),
+            base: 0,
+        }
+    }
+
+    fn millis(&self) -> i128 {
+        self.base + self.start.elapsed().as_millis() as i128
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Word> Device<W> for Clock {
+    fn read(&mut self, _offset: usize) -> W {
+        W::from_i128(self.millis()).expect("Clock reading doesn't fit in a word")
+    }
+
+    fn write(&mut self, _offset: usize, val: W) {
+        self.start = Instant::now();
+        self.base = val.to_i128();
+    }
+}
+
+/// Pseudo-random numbers from 0 to 2^31 - 1, at offset 0.
+///
+/// Every read gives the next number of an xorshift sequence, writing reseeds it. The same seed
+/// always gives the same numbers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero, so mix in a constant and avoid the one seed giving zero
+        let state = match seed ^ 0x9e37_79b9_7f4a_7c15 {
+            0 => 0x9e37_79b9_7f4a_7c15,
+            state => state,
+        };
+        Random { state }
+    }
+}
+
+impl<W: Word> Device<W> for Random {
+    fn read(&mut self, _offset: usize) -> W {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        W::from_i128(i128::from(self.state >> 33)).unwrap()
+    }
+
+    fn write(&mut self, _offset: usize, val: W) {
+        *self = Random::new(val.to_i128() as u64);
+    }
+}
+
+/// Text terminal taking two addresses.
+///
+/// Reading offset 0 takes the next character code of input, or -1 when there is none, and
+/// offset 1 gives the number of characters waiting. Writing offset 0 prints a character, values
+/// outside the ASCII range are printed as numbers on their own line. Other writes are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Console {
+    input: VecDeque<u8>,
+    text: String,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue up a line of input, with a trailing newline added if missing
+    pub fn push_line(&mut self, line: &str) {
+        self.input.extend(line.bytes());
+        if !line.ends_with('\n') {
+            self.input.push_back(b'\n');
+        }
+    }
+
+    /// Everything printed so far
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl<W: Word> Device<W> for Console {
+    fn read(&mut self, offset: usize) -> W {
+        let val = match offset {
+            0 => self.input.pop_front().map_or(-1, i128::from),
+            _ => self.input.len() as i128,
+        };
+        W::from_i128(val).unwrap()
+    }
+
+    fn write(&mut self, offset: usize, val: W) {
+        if offset != 0 {
+            return;
+        }
+        match u8::try_from(val.to_i128()) {
+            Ok(byte) if byte.is_ascii() => self.text.push(char::from(byte)),
+            _ => self.text += &format!("{}\n", val),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::asm::assemble;
+    use super::super::{Machine, State};
+    use super::*;
+
+    #[test]
+    fn console_echo() {
+        // copies console input to the console in upper case until it runs out, then outputs how
+        // many characters were left waiting when it started
+        let program = assemble(
+            "
+                    ADD [1001], #0, [waiting]
+            loop:   ADD [1000], #0, [ch]
+                    EQ [ch], #-1, [done]
+                    JT [done], #end
+                    LT [ch], #97, [lower]
+                    JT [lower], #copy
+                    ADD [ch], #-32, [ch]
+            copy:   ADD [ch], #0, [1000]
+                    JT #1, #loop
+            end:    ADD #1000, #0, [1000]
+                    OUT [waiting]
+                    HALT
+            ch:     .data 0
+            done:   .data 0
+            lower:  .data 0
+            waiting: .data 0
+            ",
+        )
+        .unwrap();
+        let mut console = Console::new();
+        console.push_line("hi there");
+        let mut machine = Machine::new(program);
+        machine.attach(1000..1002, console);
+        assert_eq!(machine.run(), Ok(State::Output(9)));
+        let console = machine.device::<Console>(1000).unwrap();
+        assert_eq!(console.text(), "HI THERE\n1000\n");
+        // the device took the writes, memory is untouched
+        assert_eq!(machine.memory()[1000], 0);
+        assert!(machine.device::<Clock>(1000).is_none());
+        assert!(machine.device::<Console>(1002).is_none());
+    }
+
+    #[test]
+    fn random_and_clock() {
+        // reads the random device three times then the clock, then reseeds and reads once more
+        let program = vec![4, 500, 4, 500, 4, 500, 4, 600, 1101, 5, 0, 500, 4, 500, 99];
+        let mut machine = Machine::new(program);
+        machine.attach(500..501, Random::new(5));
+        machine.attach(600..601, Clock::new());
+        let mut output = Vec::new();
+        machine.run_with((), &mut output).unwrap();
+
+        let mut random = Random::new(5);
+        let expected: Vec<isize> = (0..3).map(|_| random.read(0)).collect();
+        assert_eq!(output[..3], expected[..]);
+        assert!(output[..3].iter().all(|val| (0..1 << 31).contains(val)));
+        assert_ne!(output[0], output[1]);
+        // a clock read straight away should be well under a second
+        assert!((0..1000).contains(&output[3]));
+        assert_eq!(output[4], Random::new(5).read(0));
+
+        // every seed gives numbers, including the one cancelling out the mixed in constant
+        let mut random = Random::new(0x9e37_79b9_7f4a_7c15);
+        let vals: Vec<isize> = (0..2).map(|_| random.read(0)).collect();
+        assert_ne!(vals, vec![0, 0]);
+
+        // clones get their own copy of each device
+        let mut first = Machine::new(vec![4, 500, 99]);
+        first.attach(500..501, Random::new(1));
+        let mut second = first.clone();
+        assert_eq!(first.run(), second.run());
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps")]
+    fn overlapping_devices() {
+        let mut machine = Machine::new(vec![99]);
+        machine.attach(10..20, Console::new());
+        machine.attach(19..21, Clock::new());
+    }
+}
@@ -0,0 +1,170 @@
+//! Disassembler turning an IntCode memory image into an annotated listing.
+//!
+//! Each line of the listing holds the address, the instruction in assembly syntax and the raw
+//! words it was decoded from, e.g.
+//!
+//! ```text
+//!      0: MUL [4], #3, [4]               ; 1002,4,3,4
+//!      4: DATA 33                        ; 33
+//! ```
+//!
+//! Parameters are written as `[12]` for position mode, `#5` for immediate mode and `rb+3` for
+//! relative mode. Any word which doesn't decode into a complete, valid instruction is emitted as
+//! `DATA`.
+
+use std::fmt;
+
+use super::{parse_instruction, AddrMode, Word};
+
+/// A single decoded instruction or data word
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// Address of the first word
+    pub addr: usize,
+    /// Raw words the line was decoded from
+    pub words: Vec<isize>,
+    /// Assembly text, without address or annotation
+    pub text: String,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words: Vec<String> = self.words.iter().map(|w| w.to_string()).collect();
+        write!(
+            f,
+            "{:>6}: {:<30} ; {}",
+            self.addr,
+            self.text,
+            words.join(",")
+        )
+    }
+}
+
+/// Format a single parameter according to its addressing mode
+pub(super) fn format_operand(mode: &AddrMode, param: isize) -> String {
+    match mode {
+        AddrMode::Pos => format!("[{}]", param),
+        AddrMode::Imm => format!("#{}", param),
+        AddrMode::Rel if param < 0 => format!("rb{}", param),
+        AddrMode::Rel => format!("rb+{}", param),
+    }
+}
+
+/// Decode the instruction starting at `addr` into assembly text and its length in words.
+///
+/// Returns `None` if the word isn't a valid instruction, the instruction would run past the end
+/// of `mem`, or it writes through an immediate mode parameter. Words with mode digits the
+/// instruction doesn't use, which the assembler wouldn't produce, are also left as data so a
+/// listing always reassembles to the same words.
+pub fn decode(mem: &[isize], addr: usize) -> Option<(String, usize)> {
+    let word = *mem.get(addr)?;
+    let (op, mode1, mode2, mode3) = parse_instruction(word.to_i128()).ok()?;
+    let len = op.num_params() + 1;
+    let params = mem.get(addr + 1..addr + len)?;
+    let modes = [mode1, mode2, mode3];
+    let canonical = modes[..op.num_params()]
+        .iter()
+        .zip([100, 1000, 10000].iter())
+        .fold(op as isize, |acc, (mode, place)| {
+            acc + *mode as isize * place
+        });
+    if canonical != word {
+        return None;
+    }
+    if let Some(n) = op.write_param() {
+        if modes[n - 1] == AddrMode::Imm {
+            return None;
+        }
+    }
+
+    let operands: Vec<String> = params
+        .iter()
+        .zip(modes.iter())
+        .map(|(param, mode)| format_operand(mode, *param))
+        .collect();
+    let text = if operands.is_empty() {
+        op.mnemonic().to_owned()
+    } else {
+        format!("{} {}", op.mnemonic(), operands.join(", "))
+    };
+    Some((text, len))
+}
+
+/// Walk the memory image from address 0, producing one line per instruction or data word
+pub fn disassemble(mem: &[isize]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+    while addr < mem.len() {
+        let (text, len) = decode(mem, addr).unwrap_or_else(|| (format!("DATA {}", mem[addr]), 1));
+        lines.push(Line {
+            addr,
+            words: mem[addr..addr + len].to_vec(),
+            text,
+        });
+        addr += len;
+    }
+    lines
+}
+
+/// Render the full listing for a memory image, one line per instruction
+pub fn listing(mem: &[isize]) -> String {
+    disassemble(mem)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decodes_modes() {
+        let mem = [1002, 4, 3, 4, 22201, -1, 7, 2, 109, 19, 99];
+        let text: Vec<String> = disassemble(&mem)
+            .into_iter()
+            .map(|line| line.text)
+            .collect();
+        assert_eq!(
+            text,
+            vec![
+                "MUL [4], #3, [4]",
+                "ADD rb-1, rb+7, rb+2",
+                "ARB #19",
+                "HALT"
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_data() {
+        // invalid opcode, immediate write, and an instruction cut off by the end of memory
+        let mem = [0, 11101, 1, 2, 3, -5, 1, 1];
+        let lines = disassemble(&mem);
+        let text: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(
+            text,
+            vec![
+                "DATA 0",
+                "DATA 11101",
+                "ADD [2], [3], [-5]",
+                "DATA 1",
+                "DATA 1"
+            ]
+        );
+        assert_eq!(lines[2].addr, 2);
+        assert_eq!(lines[2].words, vec![1, 2, 3, -5]);
+    }
+
+    #[test]
+    fn listing_format() {
+        let mem = [104, -3, 99];
+        assert_eq!(
+            listing(&mem),
+            format!(
+                "{:>6}: {:<30} ; 104,-3\n{:>6}: {:<30} ; 99\n",
+                0, "OUT #-3", 2, "HALT"
+            )
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Errors produced while running an IntCode program.
+
+use std::error::Error;
+use std::fmt;
+
+/// Fatal problem encountered while executing an instruction.
+///
+/// Every variant records the instruction pointer and the raw instruction word at that address so
+/// the offending instruction can be found again. Words are widened to `i128` so errors look the
+/// same whatever `Word` type the machine uses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IntcodeError {
+    /// The low two digits of the instruction word are not a known opcode
+    InvalidOpcode { ip: usize, word: i128 },
+    /// Parameter `param` (counting from 1) has an unknown addressing mode digit
+    InvalidMode { ip: usize, word: i128, param: usize },
+    /// Computing an address overflowed the addressable range
+    OutOfBounds { ip: usize, word: i128 },
+    /// A parameter or jump target resolved to an address below zero
+    NegativeAddress { ip: usize, word: i128, addr: i128 },
+    /// Parameter `param` is written to, but uses immediate mode
+    ImmediateWrite { ip: usize, word: i128, param: usize },
+    /// An arithmetic instruction overflowed, only reported for `Checked` words
+    Overflow { ip: usize, word: i128 },
+    /// The program asked for input after the input source ran dry
+    InputExhausted { ip: usize, word: i128 },
+    /// The configured maximum number of instructions were executed without halting
+    StepLimit { ip: usize, word: i128, limit: usize },
+    /// The machine returned to an earlier state without any I/O, so will never halt
+    InfiniteLoop { ip: usize, word: i128 },
+    /// An opcode from a `custom::Registry` reported a problem
+    Custom {
+        ip: usize,
+        word: i128,
+        message: String,
+    },
+}
+
+impl IntcodeError {
+    /// Address of the instruction which failed
+    pub fn ip(&self) -> usize {
+        use IntcodeError::*;
+        match self {
+            InvalidOpcode { ip, .. }
+            | InvalidMode { ip, .. }
+            | OutOfBounds { ip, .. }
+            | NegativeAddress { ip, .. }
+            | ImmediateWrite { ip, .. }
+            | Overflow { ip, .. }
+            | InputExhausted { ip, .. }
+            | StepLimit { ip, .. }
+            | InfiniteLoop { ip, .. }
+            | Custom { ip, .. } => *ip,
+        }
+    }
+
+    /// Raw instruction word of the instruction which failed
+    pub fn word(&self) -> i128 {
+        use IntcodeError::*;
+        match self {
+            InvalidOpcode { word, .. }
+            | InvalidMode { word, .. }
+            | OutOfBounds { word, .. }
+            | NegativeAddress { word, .. }
+            | ImmediateWrite { word, .. }
+            | Overflow { word, .. }
+            | InputExhausted { word, .. }
+            | StepLimit { word, .. }
+            | InfiniteLoop { word, .. }
+            | Custom { word, .. } => *word,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use IntcodeError::*;
+        match self {
+            InvalidOpcode { .. } => write!(f, "invalid opcode")?,
+            InvalidMode { param, .. } => {
+                write!(f, "invalid addressing mode for parameter {}", param)?
+            }
+            OutOfBounds { .. } => write!(f, "address out of bounds")?,
+            NegativeAddress { addr, .. } => write!(f, "negative address {}", addr)?,
+            ImmediateWrite { param, .. } => {
+                write!(f, "write through immediate mode parameter {}", param)?
+            }
+            Overflow { .. } => write!(f, "arithmetic overflow")?,
+            InputExhausted { .. } => write!(f, "input exhausted")?,
+            StepLimit { limit, .. } => write!(f, "step limit of {} reached", limit)?,
+            InfiniteLoop { .. } => write!(f, "infinite loop detected")?,
+            Custom { message, .. } => write!(f, "{}", message)?,
+        }
+        write!(f, " (ip: {}, instruction: {})", self.ip(), self.word())
+    }
+}
+
+impl Error for IntcodeError {}
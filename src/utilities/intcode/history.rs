@@ -0,0 +1,96 @@
+//! Log of what each executed instruction changed, kept by a `Machine` with history turned on.
+//!
+//! Every instruction writes at most one memory cell, so a record holds the old and new value of
+//! that cell along with the registers before the instruction ran and any input it consumed. That
+//! is enough for the machine to undo instructions one at a time, and to answer which instruction
+//! last wrote a given address, which is handy for following self-modifying code.
+
+/// A single memory write made by an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Write<W = isize> {
+    pub addr: usize,
+    pub old: W,
+    pub new: W,
+}
+
+/// Everything needed to undo one executed instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record<W = isize> {
+    /// Instructions executed before this one
+    pub step: usize,
+    pub ip: usize,
+    /// Relative base before the instruction ran
+    pub relative_base: W,
+    pub write: Option<Write<W>>,
+    /// Value taken from the input queue
+    pub input: Option<W>,
+    /// Value produced by an output instruction
+    pub output: Option<W>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct History<W = isize> {
+    records: Vec<Record<W>>,
+    // write made by the instruction currently executing, filled in by `Machine::write`
+    pending: Option<Write<W>>,
+}
+
+impl<W: Copy> History<W> {
+    pub fn new() -> Self {
+        History {
+            records: Vec::new(),
+            pending: None,
+        }
+    }
+
+    pub(super) fn write(&mut self, addr: usize, old: W, new: W) {
+        self.pending = Some(Write { addr, old, new });
+    }
+
+    /// Log a completed instruction, taking the write noted since the last one
+    pub(super) fn record(
+        &mut self,
+        step: usize,
+        ip: usize,
+        relative_base: W,
+        input: Option<W>,
+        output: Option<W>,
+    ) {
+        self.records.push(Record {
+            step,
+            ip,
+            relative_base,
+            write: self.pending.take(),
+            input,
+            output,
+        });
+    }
+
+    pub(super) fn pop(&mut self) -> Option<Record<W>> {
+        self.records.pop()
+    }
+
+    /// Every logged instruction, oldest first
+    pub fn records(&self) -> &[Record<W>] {
+        &self.records
+    }
+
+    /// Earliest step the machine can be rewound to
+    pub fn first_step(&self) -> Option<usize> {
+        self.records.first().map(|record| record.step)
+    }
+
+    /// Most recent instruction to write `addr`, `None` if nothing logged has written it
+    pub fn last_write(&self, addr: usize) -> Option<&Record<W>> {
+        self.records
+            .iter()
+            .rev()
+            .find(|record| record.write.map(|write| write.addr) == Some(addr))
+    }
+}
+
+impl<W: Copy> Default for History<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,67 @@
+//! Guards against programs which never halt.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use super::{Memory, Word};
+
+/// Execution limits applied to a `Machine`, by default there are none
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Fail with `IntcodeError::StepLimit` once this many instructions have executed
+    pub max_steps: Option<usize>,
+    /// Fail with `IntcodeError::InfiniteLoop` if the machine returns to an earlier state without
+    /// any input or output happening in between
+    pub detect_loops: bool,
+}
+
+/// Tracks machine states seen since the last I/O.
+///
+/// A state is the instruction pointer, relative base and a hash of memory. The memory hash is the
+/// wrapping sum of a hash of every non-zero `(address, value)` pair, which lets each write update
+/// it in constant time. A repeated state means the program is stuck, barring a 64 bit collision.
+#[derive(Clone, Debug)]
+pub(super) struct LoopDetector<W> {
+    mem_hash: u64,
+    seen: HashSet<(usize, W, u64)>,
+}
+
+fn cell_hash<W: Word>(addr: usize, val: W) -> u64 {
+    // zero cells don't contribute, so memory growing with zeroes leaves the hash alone
+    if val == W::ZERO {
+        return 0;
+    }
+    let mut hasher = DefaultHasher::new();
+    (addr, val).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<W: Word> LoopDetector<W> {
+    pub(super) fn new(mem: &Memory<W>) -> Self {
+        LoopDetector {
+            mem_hash: mem
+                .iter()
+                .fold(0, |acc, (addr, val)| acc.wrapping_add(cell_hash(addr, val))),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Record that `addr` is changing from `old` to `new`
+    pub(super) fn write(&mut self, addr: usize, old: W, new: W) {
+        self.mem_hash = self
+            .mem_hash
+            .wrapping_sub(cell_hash(addr, old))
+            .wrapping_add(cell_hash(addr, new));
+    }
+
+    /// Forget earlier states, I/O means the program may behave differently from here on
+    pub(super) fn io(&mut self) {
+        self.seen.clear();
+    }
+
+    /// Record the current state, returning false if it has been seen before
+    pub(super) fn visit(&mut self, ip: usize, relative_base: W) -> bool {
+        self.seen.insert((ip, relative_base, self.mem_hash))
+    }
+}
@@ -0,0 +1,1047 @@
+//! A resumable IntCode machine.
+//!
+//! Unlike `interpret`, which runs a program to completion, a `Machine` pauses whenever it needs
+//! input or produces output. This lets callers drive several machines cooperatively, feeding the
+//! output of one into the input of another.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::coverage::Coverage;
+use super::custom::{Call, Custom, Registry, Role};
+use super::device::Device;
+use super::history::{History, Record};
+use super::limits::{Limits, LoopDetector};
+use super::profile::Profile;
+use super::snapshot::Snapshot;
+use super::{parse_instruction, AddrMode, Input, IntcodeError, Memory, OpCode, Output, Poll, Word};
+
+/// Reason `Machine::run` returned control to the caller
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State<W = isize> {
+    /// Program is trying to read, but no input is queued; push some and run again
+    NeedsInput,
+    /// Program wrote a value, running again continues from the following instruction
+    Output(W),
+    /// Program reached opcode 99, running again does nothing
+    Halted,
+}
+
+/// Opcode and parameter modes of a decoded instruction word
+type Decoded = (OpCode, AddrMode, AddrMode, AddrMode);
+
+#[derive(Debug)]
+enum IPChange {
+    Delta(usize),
+    New(usize),
+}
+
+/// IntCode machine owning its memory, instruction pointer, relative base and pending input.
+#[derive(Clone, Debug)]
+pub struct Machine<W = isize> {
+    mem: Memory<W>,
+    ip: usize,
+    relative_base: W,
+    input: VecDeque<W>,
+    steps: usize,
+    limits: Limits,
+    // built lazily when loop detection is on, dropped whenever memory is changed from outside
+    detector: Option<LoopDetector<W>>,
+    // decoded instruction words by address, `None` when caching is turned off. An entry is
+    // dropped whenever its cell is written, so self-modifying code is decoded afresh.
+    decoded: Option<Vec<Option<Decoded>>>,
+    profile: Option<Profile>,
+    coverage: Option<Coverage>,
+    history: Option<History<W>>,
+    // attached by `attach`, kept sorted by address
+    devices: Vec<(Range<usize>, Box<dyn Device<W>>)>,
+    opcodes: Registry<W>,
+}
+
+impl Machine {
+    pub fn new(mem: impl Into<Memory>) -> Self {
+        Machine::from_memory(mem.into())
+    }
+}
+
+impl<W: Word> From<Snapshot<W>> for Machine<W> {
+    fn from(snapshot: Snapshot<W>) -> Self {
+        let mut machine = Machine::from_memory(Memory::new());
+        machine.restore(snapshot);
+        machine
+    }
+}
+
+impl<W: Word> Machine<W> {
+    /// Machine running with any `Word` type, `new` is a shorthand for `isize` machines
+    pub fn from_memory(mem: Memory<W>) -> Self {
+        Machine {
+            mem,
+            ip: 0,
+            relative_base: W::ZERO,
+            input: VecDeque::new(),
+            steps: 0,
+            limits: Limits::default(),
+            detector: None,
+            decoded: Some(Vec::new()),
+            profile: None,
+            coverage: None,
+            history: None,
+            devices: Vec::new(),
+            opcodes: Registry::new(),
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.detector = None;
+    }
+
+    /// Queue a value to be consumed by the next input instruction
+    pub fn push_input(&mut self, val: W) {
+        self.input.push_back(val);
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.mem
+    }
+
+    /// Mutable access to memory, changes made through it can't be undone so history restarts
+    pub fn memory_mut(&mut self) -> &mut Memory<W> {
+        self.detector = None;
+        self.forget_history();
+        if let Some(cache) = self.decoded.as_mut() {
+            cache.clear();
+        }
+        &mut self.mem
+    }
+
+    /// Change a single memory cell.
+    ///
+    /// Unlike `memory_mut` this keeps the rest of the decoded instruction cache. Any device
+    /// attached over `addr` is bypassed.
+    pub fn set_memory(&mut self, addr: usize, val: W) {
+        self.write_memory(addr, val);
+        self.forget_history();
+    }
+
+    /// Turn the decoded instruction cache on or off, it is on by default
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decoded = match enabled {
+            true => Some(Vec::new()),
+            false => None,
+        };
+    }
+
+    /// Turn profiling on or off, turning it on starts a fresh profile
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = match enabled {
+            true => Some(Profile::new()),
+            false => None,
+        };
+    }
+
+    /// Statistics on the instructions executed since profiling was turned on
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Turn coverage recording on or off, turning it on starts with nothing covered
+    pub fn set_coverage(&mut self, enabled: bool) {
+        self.coverage = match enabled {
+            true => Some(Coverage::new()),
+            false => None,
+        };
+    }
+
+    /// Addresses used as code and data since coverage was turned on
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Map `device` into the addresses in `range`, see `intcode::device`.
+    ///
+    /// Panics if `range` overlaps a device already attached.
+    pub fn attach(&mut self, range: Range<usize>, device: impl Device<W> + 'static) {
+        let at = self
+            .devices
+            .iter()
+            .position(|(other, _)| range.start < other.end)
+            .unwrap_or(self.devices.len());
+        if let Some((other, _)) = self.devices.get(at) {
+            assert!(
+                range.end <= other.start,
+                "Device at {:?} overlaps one at {:?}",
+                range,
+                other
+            );
+        }
+        self.devices.insert(at, (range, Box::new(device)));
+    }
+
+    /// The device of type `D` attached over `addr`, if any
+    pub fn device<D: Device<W> + 'static>(&self, addr: usize) -> Option<&D> {
+        let (_, device) = self
+            .devices
+            .iter()
+            .find(|(range, _)| range.contains(&addr))?;
+        device.as_any().downcast_ref()
+    }
+
+    pub fn device_mut<D: Device<W> + 'static>(&mut self, addr: usize) -> Option<&mut D> {
+        let (_, device) = self
+            .devices
+            .iter_mut()
+            .find(|(range, _)| range.contains(&addr))?;
+        device.as_any_mut().downcast_mut()
+    }
+
+    /// Run the opcodes in `opcodes` as well as the built in ones, see `intcode::custom`
+    pub fn set_opcodes(&mut self, opcodes: Registry<W>) {
+        self.opcodes = opcodes;
+    }
+
+    /// Turn the history needed by `step_back` on or off, turning it on starts an empty history.
+    ///
+    /// The history grows by one record per instruction executed.
+    pub fn set_history(&mut self, enabled: bool) {
+        self.history = match enabled {
+            true => Some(History::new()),
+            false => None,
+        };
+    }
+
+    /// Instructions executed since history was turned on, or memory was last changed from outside
+    pub fn history(&self) -> Option<&History<W>> {
+        self.history.as_ref()
+    }
+
+    fn forget_history(&mut self) {
+        if let Some(history) = self.history.as_mut() {
+            *history = History::new();
+        }
+    }
+
+    /// Undo the most recent instruction in the history, returning its record.
+    ///
+    /// Memory, registers, step count and the input queue are put back as they were before the
+    /// instruction ran. Output already handed to the caller and the profile are left alone.
+    pub fn step_back(&mut self) -> Option<Record<W>> {
+        let record = self.history.as_mut()?.pop()?;
+        if let Some(write) = record.write {
+            if let Some(entry) = self
+                .decoded
+                .as_mut()
+                .and_then(|cache| cache.get_mut(write.addr))
+            {
+                *entry = None;
+            }
+            self.mem.set(write.addr, write.old);
+        }
+        if let Some(val) = record.input {
+            self.input.push_front(val);
+        }
+        self.ip = record.ip;
+        self.relative_base = record.relative_base;
+        self.steps = record.step;
+        self.detector = None;
+        Some(record)
+    }
+
+    /// Step back until `steps()` is `step`.
+    ///
+    /// Returns false without changing anything if the history doesn't reach back that far.
+    pub fn rewind(&mut self, step: usize) -> bool {
+        let first = self.history.as_ref().and_then(History::first_step);
+        match first {
+            Some(first) if first <= step && step <= self.steps => {
+                while self.steps > step {
+                    self.step_back();
+                }
+                true
+            }
+            _ => step == self.steps,
+        }
+    }
+
+    /// Decode every valid instruction word in the program up front.
+    ///
+    /// Worthwhile when one machine is cloned to run many times, as the clones share the work.
+    pub fn predecode(&mut self) {
+        if let Some(cache) = self.decoded.as_mut() {
+            *cache = self
+                .mem
+                .as_slice()
+                .iter()
+                .map(|word| parse_instruction(word.to_i128()).ok())
+                .collect();
+        }
+    }
+
+    pub fn into_memory(self) -> Memory<W> {
+        self.mem
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> W {
+        self.relative_base
+    }
+
+    /// Number of instructions executed so far
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Copy of the state needed to carry on from this point later
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            memory: self.mem.clone(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            steps: self.steps,
+            input: self.input.iter().copied().collect(),
+        }
+    }
+
+    /// Replace the machine's state with `snapshot`, keeping its limits, profiling and devices
+    pub fn restore(&mut self, snapshot: Snapshot<W>) {
+        *self.memory_mut() = snapshot.memory;
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base;
+        self.steps = snapshot.steps;
+        self.input = snapshot.input.into_iter().collect();
+    }
+
+    /// Run until the program needs input, produces output or halts.
+    pub fn run(&mut self) -> Result<State<W>, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+    }
+
+    /// Run, pulling input from `input` and sending output to `output` as needed.
+    ///
+    /// Input is polled without blocking. If the source has nothing available yet the machine
+    /// pauses and `State::NeedsInput` is returned, running again later picks up where it left
+    /// off. Otherwise this runs until the program halts or the input source is closed.
+    pub fn run_with(
+        &mut self,
+        mut input: impl Input<W>,
+        mut output: impl Output<W>,
+    ) -> Result<State<W>, IntcodeError> {
+        loop {
+            match self.run()? {
+                State::NeedsInput => match input.poll_input() {
+                    Poll::Ready(val) => self.push_input(val),
+                    Poll::Pending => return Ok(State::NeedsInput),
+                    Poll::Closed => {
+                        return Err(IntcodeError::InputExhausted {
+                            ip: self.ip,
+                            word: self.mem[self.ip].to_i128(),
+                        })
+                    }
+                },
+                State::Output(val) => output.write_word(val),
+                State::Halted => return Ok(State::Halted),
+            }
+        }
+    }
+
+    /// Execute a single instruction.
+    ///
+    /// Returns `None` if the instruction completed without needing the caller's attention. When
+    /// input is needed, the machine is halted, or an error occurs the instruction pointer is left
+    /// where it is.
+    pub fn step(&mut self) -> Result<Option<State<W>>, IntcodeError> {
+        use OpCode::*;
+
+        let ip = self.ip;
+        let word = self.mem[ip].to_i128();
+        if let Some(custom) = self.opcodes.lookup(word) {
+            return self.step_custom(ip, word, custom);
+        }
+        let (op, addr1, addr2, addr3) = self.decode(ip)?;
+        // neither of these count as executing an instruction
+        if op == Halt {
+            // reaching it still shows the halt is live code
+            if let Some(coverage) = self.coverage.as_mut() {
+                coverage.record(ip, 1, &[], None);
+            }
+            return Ok(Some(State::Halted));
+        }
+        if op == ReadIn && self.input.is_empty() {
+            return Ok(Some(State::NeedsInput));
+        }
+
+        self.check_limits(ip, word)?;
+
+        let relative_base = self.relative_base;
+        let modes = [addr1, addr2, addr3];
+        // parameters are resolved up front, as the instruction may overwrite them
+        let accesses = self.coverage.as_ref().map(|_| {
+            let roles: Vec<Role> = (1..=op.num_params())
+                .map(|n| match op.write_param() == Some(n) {
+                    true => Role::Write,
+                    false => Role::Read,
+                })
+                .collect();
+            self.accesses(&modes, &roles)
+        });
+        let mut input = None;
+        let mut output = None;
+
+        let change = match op {
+            Add => {
+                let val = self
+                    .read_arg(1, &addr1)?
+                    .try_add(self.read_arg(2, &addr2)?)
+                    .ok_or_else(|| self.overflow())?;
+                let addr = self.write_addr(3, &addr3)?;
+                self.write(addr, val);
+                IPChange::Delta(4)
+            }
+            Multiply => {
+                let val = self
+                    .read_arg(1, &addr1)?
+                    .try_mul(self.read_arg(2, &addr2)?)
+                    .ok_or_else(|| self.overflow())?;
+                let addr = self.write_addr(3, &addr3)?;
+                self.write(addr, val);
+                IPChange::Delta(4)
+            }
+            ReadIn => {
+                let addr = self.write_addr(1, &addr1)?;
+                let val = self.input.pop_front().unwrap();
+                self.write(addr, val);
+                input = Some(val);
+                if let Some(detector) = self.detector.as_mut() {
+                    detector.io();
+                }
+                IPChange::Delta(2)
+            }
+            WriteOut => {
+                output = Some(State::Output(self.read_arg(1, &addr1)?));
+                if let Some(detector) = self.detector.as_mut() {
+                    detector.io();
+                }
+                IPChange::Delta(2)
+            }
+            JmpIfTrue => {
+                if self.read_arg(1, &addr1)? != W::ZERO {
+                    let target = self.read_arg(2, &addr2)?;
+                    IPChange::New(self.to_addr(target)?)
+                } else {
+                    IPChange::Delta(3)
+                }
+            }
+            JmpIfFalse => {
+                if self.read_arg(1, &addr1)? == W::ZERO {
+                    let target = self.read_arg(2, &addr2)?;
+                    IPChange::New(self.to_addr(target)?)
+                } else {
+                    IPChange::Delta(3)
+                }
+            }
+            LessThan => {
+                let val = if self.read_arg(1, &addr1)? < self.read_arg(2, &addr2)? {
+                    W::ONE
+                } else {
+                    W::ZERO
+                };
+                let addr = self.write_addr(3, &addr3)?;
+                self.write(addr, val);
+                IPChange::Delta(4)
+            }
+            Equals => {
+                let val = if self.read_arg(1, &addr1)? == self.read_arg(2, &addr2)? {
+                    W::ONE
+                } else {
+                    W::ZERO
+                };
+                let addr = self.write_addr(3, &addr3)?;
+                self.write(addr, val);
+                IPChange::Delta(4)
+            }
+            AdjustBase => {
+                let offset = self.read_arg(1, &addr1)?;
+                self.relative_base = self.offset_base(offset)?;
+                IPChange::Delta(2)
+            }
+            Halt => unreachable!(),
+        };
+
+        self.ip = match change {
+            IPChange::Delta(delta) => self.ip + delta,
+            IPChange::New(new) => new,
+        };
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(ip, op, modes);
+        }
+        let produced = match output {
+            Some(State::Output(val)) => Some(val),
+            _ => None,
+        };
+        let len = op.num_params() + 1;
+        self.finish_step(ip, len, relative_base, accesses, input, produced);
+        Ok(output)
+    }
+
+    /// Execute an instruction with an opcode from the registry
+    fn step_custom(
+        &mut self,
+        ip: usize,
+        word: i128,
+        custom: Arc<Custom<W>>,
+    ) -> Result<Option<State<W>>, IntcodeError> {
+        let mut modes = [AddrMode::Pos; 3];
+        for (n, mode) in (1..=custom.roles.len()).zip(modes.iter_mut()) {
+            let digit = (word / 10i128.pow(n as u32 + 1) % 10) as isize;
+            *mode = AddrMode::try_from(digit).map_err(|_| IntcodeError::InvalidMode {
+                ip,
+                word,
+                param: n,
+            })?;
+        }
+        let modes = &modes[..custom.roles.len()];
+        self.check_limits(ip, word)?;
+
+        let relative_base = self.relative_base;
+        let accesses = self
+            .coverage
+            .as_ref()
+            .map(|_| self.accesses(modes, &custom.roles));
+        let mut args = [None; 3];
+        for (n, (mode, role)) in modes.iter().zip(custom.roles.iter()).enumerate() {
+            if *role != Role::Write {
+                args[n] = Some(self.read_arg(n + 1, mode)?);
+            }
+        }
+        let mut call = Call::new(relative_base, &custom.roles, args);
+        custom
+            .exec(&mut call)
+            .map_err(|message| IntcodeError::Custom { ip, word, message })?;
+
+        // resolve everything which can fail before changing anything
+        let result = match (call.result, custom.write_param()) {
+            (Some(val), Some(n)) => Some((self.write_addr(n, &modes[n - 1])?, val)),
+            _ => None,
+        };
+        let len = custom.roles.len() + 1;
+        let next = match call.jump {
+            Some(addr) => self.to_addr(addr)?,
+            None => ip + len,
+        };
+        if let Some((addr, val)) = result {
+            self.write(addr, val);
+        }
+        self.ip = next;
+        self.relative_base = call.relative_base();
+        if call.output.is_some() {
+            if let Some(detector) = self.detector.as_mut() {
+                detector.io();
+            }
+        }
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record_custom(ip, custom.mnemonic, modes, call.output.is_some());
+        }
+        self.finish_step(ip, len, relative_base, accesses, None, call.output);
+        Ok(call.output.map(State::Output))
+    }
+
+    /// Fail if running the instruction at `ip` would break the machine's limits
+    fn check_limits(&mut self, ip: usize, word: i128) -> Result<(), IntcodeError> {
+        if let Some(limit) = self.limits.max_steps {
+            if self.steps >= limit {
+                return Err(IntcodeError::StepLimit { ip, word, limit });
+            }
+        }
+        if self.limits.detect_loops {
+            let mem = &self.mem;
+            let detector = self.detector.get_or_insert_with(|| LoopDetector::new(mem));
+            if !detector.visit(ip, self.relative_base) {
+                return Err(IntcodeError::InfiniteLoop { ip, word });
+            }
+        }
+        Ok(())
+    }
+
+    /// Note an executed instruction in the coverage and history, then count it
+    fn finish_step(
+        &mut self,
+        ip: usize,
+        len: usize,
+        relative_base: W,
+        accesses: Option<(Vec<usize>, Option<usize>)>,
+        input: Option<W>,
+        output: Option<W>,
+    ) {
+        if let (Some(coverage), Some((reads, write))) = (self.coverage.as_mut(), accesses) {
+            coverage.record(ip, len, &reads, write);
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.record(self.steps, ip, relative_base, input, output);
+        }
+        self.steps += 1;
+    }
+
+    /// Decode the instruction word at `addr`, going through the cache when it is on
+    fn decode(&mut self, addr: usize) -> Result<Decoded, IntcodeError> {
+        if let Some(Some(decoded)) = self.decoded.as_ref().and_then(|cache| cache.get(addr)) {
+            return Ok(*decoded);
+        }
+        let word = self.mem[addr].to_i128();
+        let decoded = parse_instruction(word).map_err(|err| err.at(addr, word))?;
+        // code lives in the dense region, so don't grow the cache to cover sparse addresses
+        if let Some(cache) = self.decoded.as_mut() {
+            let len = self.mem.as_slice().len();
+            if addr < len {
+                if cache.len() < len {
+                    cache.resize(len, None);
+                }
+                cache[addr] = Some(decoded);
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Addresses the current instruction's parameters read from and write to
+    fn accesses(&self, modes: &[AddrMode], roles: &[Role]) -> (Vec<usize>, Option<usize>) {
+        let mut reads = Vec::new();
+        let mut write = None;
+        for (n, (mode, role)) in (1..).zip(modes.iter().zip(roles)) {
+            if *mode == AddrMode::Imm {
+                continue;
+            }
+            // a bad address fails the instruction, so it never gets recorded
+            if let Ok(addr) = self.write_addr(n, mode) {
+                if *role != Role::Write {
+                    reads.push(addr);
+                }
+                if *role != Role::Read {
+                    write = Some(addr);
+                }
+            }
+        }
+        (reads, write)
+    }
+
+    /// Store `val` at `addr`, every write made by an instruction goes through here
+    fn write(&mut self, addr: usize, val: W) {
+        if let Some(device) = self.device_at(addr) {
+            device.write(addr, val);
+            return;
+        }
+        self.write_memory(addr, val);
+    }
+
+    fn write_memory(&mut self, addr: usize, val: W) {
+        if let Some(detector) = self.detector.as_mut() {
+            detector.write(addr, self.mem[addr], val);
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.write(addr, self.mem[addr], val);
+        }
+        if let Some(entry) = self.decoded.as_mut().and_then(|cache| cache.get_mut(addr)) {
+            *entry = None;
+        }
+        self.mem.set(addr, val);
+    }
+
+    /// Read the value of the `n`th parameter of the current instruction, honoring its addressing
+    /// mode.
+    fn read_arg(&mut self, n: usize, mode: &AddrMode) -> Result<W, IntcodeError> {
+        match mode {
+            AddrMode::Imm => Ok(self.mem[self.ip + n]),
+            _ => {
+                let addr = self.write_addr(n, mode)?;
+                match self.device_at(addr) {
+                    Some(device) => Ok(device.read(addr)),
+                    None => Ok(self.mem[addr]),
+                }
+            }
+        }
+    }
+
+    /// The device mapped over `addr` with accesses made relative to it, noting the I/O
+    fn device_at(&mut self, addr: usize) -> Option<Mapped<'_, W>> {
+        let (range, device) = self
+            .devices
+            .iter_mut()
+            .find(|(range, _)| range.contains(&addr))?;
+        if let Some(detector) = self.detector.as_mut() {
+            detector.io();
+        }
+        Some(Mapped {
+            start: range.start,
+            device: device.as_mut(),
+        })
+    }
+
+    /// Find the address the `n`th parameter of the current instruction refers to.
+    ///
+    /// Fails for immediate mode parameters, as there is nowhere to write to
+    fn write_addr(&self, n: usize, mode: &AddrMode) -> Result<usize, IntcodeError> {
+        let param = self.mem[self.ip + n];
+        match mode {
+            AddrMode::Pos => self.to_addr(param),
+            AddrMode::Rel => self.to_addr(self.offset_base(param)?),
+            AddrMode::Imm => Err(IntcodeError::ImmediateWrite {
+                ip: self.ip,
+                word: self.mem[self.ip].to_i128(),
+                param: n,
+            }),
+        }
+    }
+
+    /// Relative base plus `offset`, which must fit in a word whatever kind of word it is
+    fn offset_base(&self, offset: W) -> Result<W, IntcodeError> {
+        self.relative_base
+            .to_i128()
+            .checked_add(offset.to_i128())
+            .and_then(W::from_i128)
+            .ok_or_else(|| self.out_of_bounds())
+    }
+
+    /// Convert a computed address into an index into memory
+    fn to_addr(&self, addr: W) -> Result<usize, IntcodeError> {
+        let addr = addr.to_i128();
+        if addr < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                ip: self.ip,
+                word: self.mem[self.ip].to_i128(),
+                addr,
+            });
+        }
+        usize::try_from(addr).map_err(|_| self.out_of_bounds())
+    }
+
+    fn out_of_bounds(&self) -> IntcodeError {
+        IntcodeError::OutOfBounds {
+            ip: self.ip,
+            word: self.mem[self.ip].to_i128(),
+        }
+    }
+
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow {
+            ip: self.ip,
+            word: self.mem[self.ip].to_i128(),
+        }
+    }
+}
+
+/// Device found by `Machine::device_at`, taking addresses rather than offsets
+struct Mapped<'a, W> {
+    start: usize,
+    device: &'a mut dyn Device<W>,
+}
+
+impl<'a, W> Mapped<'a, W> {
+    fn read(self, addr: usize) -> W {
+        self.device.read(addr - self.start)
+    }
+
+    fn write(self, addr: usize, val: W) {
+        self.device.write(addr - self.start, val)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{day05, Write};
+    use super::*;
+
+    #[test]
+    fn pauses_for_io() {
+        // Reads two values and outputs their sum, then their product
+        let mut machine = Machine::new(vec![
+            3, 17, 3, 18, 1, 17, 18, 19, 4, 19, 2, 17, 18, 19, 4, 19, 99,
+        ]);
+        assert_eq!(machine.run(), Ok(State::NeedsInput));
+        assert_eq!(machine.run(), Ok(State::NeedsInput));
+        machine.push_input(6);
+        assert_eq!(machine.run(), Ok(State::NeedsInput));
+        machine.push_input(7);
+        assert_eq!(machine.run(), Ok(State::Output(13)));
+        assert_eq!(machine.run(), Ok(State::Output(42)));
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert_eq!(machine.run(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn decode_cache() {
+        // runs the instruction at 0 as an ADD, rewrites it into a MUL and runs it again
+        let program = vec![
+            1101, 3, 4, 30, 4, 30, 1008, 0, 1102, 31, 1005, 31, 20, 1101, 1100, 2, 0, 1105, 1, 0,
+            99, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut machine = Machine::new(program.clone());
+        machine.predecode();
+        for cached in [true, false].iter() {
+            let mut machine = machine.clone();
+            if !cached {
+                machine.set_decode_cache(false);
+            }
+            let mut output = Vec::new();
+            assert_eq!(machine.run_with((), &mut output), Ok(State::Halted));
+            assert_eq!(output, vec![7, 12]);
+        }
+
+        // changing a cell from outside must also be seen
+        machine.set_memory(0, 1102);
+        let mut output = Vec::new();
+        assert_eq!(machine.run_with((), &mut output), Ok(State::Halted));
+        assert_eq!(output, vec![12]);
+        let mut machine = Machine::new(program);
+        machine.predecode();
+        machine.memory_mut()[0] = 1102;
+        assert_eq!(machine.run(), Ok(State::Output(12)));
+    }
+
+    #[test]
+    fn time_travel() {
+        let program = Memory::from(day05());
+        let mut machine = Machine::new(program.clone());
+        machine.set_history(true);
+        machine.push_input(5);
+        assert_eq!(machine.run(), Ok(State::Output(8805067)));
+        let steps = machine.steps();
+
+        // the system ID is added into the word at 6, turning it into a jump
+        let record = *machine.history().unwrap().last_write(6).unwrap();
+        assert_eq!((record.step, record.ip), (1, 2));
+        assert_eq!(
+            record.write,
+            Some(Write {
+                addr: 6,
+                old: 1100,
+                new: 1105
+            })
+        );
+
+        let last = machine.step_back().unwrap();
+        assert_eq!(last.output, Some(8805067));
+        assert_eq!(machine.steps(), steps - 1);
+        assert!(!machine.rewind(steps));
+        assert!(machine.rewind(1));
+        assert_eq!(machine.memory()[6], 1100);
+        assert_eq!(machine.ip(), 2);
+        assert!(machine.rewind(0));
+        assert_eq!(machine.memory(), &program);
+        assert_eq!(machine.step_back(), None);
+
+        // the consumed input is queued again, so the run repeats
+        assert_eq!(machine.run(), Ok(State::Output(8805067)));
+        assert_eq!(machine.steps(), steps);
+        machine.set_memory(0, 3);
+        assert_eq!(machine.history().unwrap().records(), &[]);
+    }
+
+    /// Compare running with and without the decode cache on the day 2 and day 5 inputs, the
+    /// fastest of several trials is reported.
+    ///
+    /// Run with `cargo test --release decode_cache_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn decode_cache_benchmark() {
+        use std::time::{Duration, Instant};
+
+        let load = |path: &str| -> Machine {
+            Machine::new(
+                std::fs::read_to_string(path)
+                    .unwrap()
+                    .trim()
+                    .parse::<Memory>()
+                    .unwrap(),
+            )
+        };
+        let fastest = |run: &dyn Fn()| -> Duration {
+            (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+                    run();
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+        // brute force search for the noun and verb, the same work day 2 part 2 does
+        let day2 = |template: &Machine| {
+            for noun in 0..100 {
+                for verb in 0..100 {
+                    let mut machine = template.clone();
+                    machine.set_memory(1, noun);
+                    machine.set_memory(2, verb);
+                    let _ = machine.run();
+                }
+            }
+        };
+        let day5 = |template: &Machine| {
+            for _ in 0..1000 {
+                for system in [1, 5].iter() {
+                    let mut output = Vec::new();
+                    template.clone().run_with(*system, &mut output).unwrap();
+                }
+            }
+        };
+
+        for (name, path, work) in [
+            ("day 2", "inputs/day02.txt", &day2 as &dyn Fn(&Machine)),
+            ("day 5 x1000", "inputs/day05.txt", &day5),
+        ]
+        .iter()
+        {
+            let plain = {
+                let mut template = load(path);
+                template.set_decode_cache(false);
+                fastest(&|| work(&template))
+            };
+            let cached = {
+                let mut template = load(path);
+                template.predecode();
+                fastest(&|| work(&template))
+            };
+            println!(
+                "{}: {:?} uncached, {:?} cached ({:.2}x)",
+                name,
+                plain,
+                cached,
+                plain.as_secs_f64() / cached.as_secs_f64()
+            );
+        }
+    }
+
+    #[test]
+    fn day7_feedback_loop() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let mut amps: Vec<Machine> = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|phase| {
+                let mut amp = Machine::new(program.clone());
+                amp.push_input(*phase);
+                amp
+            })
+            .collect();
+
+        let mut signal = 0;
+        'feedback: loop {
+            for amp in amps.iter_mut() {
+                amp.push_input(signal);
+                match amp.run().unwrap() {
+                    State::Output(val) => signal = val,
+                    State::Halted => break 'feedback,
+                    State::NeedsInput => panic!("amplifier stalled waiting for input"),
+                }
+            }
+        }
+        assert_eq!(signal, 139629729);
+    }
+
+    #[test]
+    fn day7_feedback_loop_polled() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let mut amps = vec![Machine::new(program); 5];
+        let mut queues: Vec<VecDeque<isize>> = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|phase| vec![*phase].into())
+            .collect();
+        queues[0].push_back(0);
+
+        // run each amplifier until it blocks on an empty queue, passing its output along
+        let mut halted = 0;
+        while halted < amps.len() {
+            halted = 0;
+            for i in 0..amps.len() {
+                let mut output = Vec::new();
+                if amps[i].run_with(&mut queues[i], &mut output) == Ok(State::Halted) {
+                    halted += 1;
+                }
+                let next = (i + 1) % amps.len();
+                queues[next].extend(output);
+            }
+        }
+        assert_eq!(queues[0], vec![139629729]);
+    }
+
+    #[test]
+    fn closed_input_is_an_error() {
+        let mut machine = Machine::new(vec![3, 0, 3, 0, 99]);
+        let input: &[isize] = &[1];
+        assert_eq!(
+            machine.run_with(input, ()),
+            Err(IntcodeError::InputExhausted { ip: 2, word: 3 })
+        );
+    }
+
+    #[test]
+    fn step_limit() {
+        // counts down from 3 then halts, taking 6 instructions
+        let program = vec![1001, 9, -1, 9, 1005, 9, 0, 99, 0, 3];
+        let mut machine = Machine::new(program.clone());
+        machine.set_limits(Limits {
+            max_steps: Some(7),
+            ..Limits::default()
+        });
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert_eq!(machine.steps(), 6);
+
+        let mut machine = Machine::new(program);
+        machine.set_limits(Limits {
+            max_steps: Some(4),
+            ..Limits::default()
+        });
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::StepLimit {
+                ip: 0,
+                word: 1001,
+                limit: 4
+            })
+        );
+    }
+
+    #[test]
+    fn loop_detection() {
+        let limits = Limits {
+            detect_loops: true,
+            ..Limits::default()
+        };
+
+        // jumps back to itself forever
+        let mut machine = Machine::new(vec![1105, 1, 0]);
+        machine.set_limits(limits);
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::InfiniteLoop { ip: 0, word: 1105 })
+        );
+
+        // counting loops change memory each time around, so must not be flagged
+        let mut machine = Machine::new(vec![1001, 9, -1, 9, 1005, 9, 0, 99, 0, 3]);
+        machine.set_limits(limits);
+        assert_eq!(machine.run(), Ok(State::Halted));
+
+        // outputting forever is not a loop without I/O, so only the step limit catches it
+        let mut machine = Machine::new(vec![104, 1, 1105, 1, 0]);
+        machine.set_limits(Limits {
+            max_steps: Some(100),
+            detect_loops: true,
+        });
+        for _ in 0..50 {
+            assert_eq!(machine.run(), Ok(State::Output(1)));
+        }
+        assert!(matches!(machine.run(), Err(IntcodeError::StepLimit { .. })));
+    }
+}
@@ -0,0 +1,252 @@
+//! Memory used by the IntCode interpreter.
+
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+use std::num::ParseIntError;
+use std::ops::{Index, IndexMut};
+use std::str::FromStr;
+
+use super::Word;
+
+/// Writes landing within this many words past the end of the dense region will grow it, anything
+/// further out is kept in the sparse map instead
+const MAX_DENSE_GROWTH: usize = 4096;
+
+/// Zero-filled memory which grows on demand.
+///
+/// The loaded program and anything written close to it live in a `Vec`, while writes to far away
+/// addresses are stored sparsely so that a program poking at address 10^12 doesn't allocate
+/// terabytes. Every address reads as 0 until it is written.
+#[derive(Clone, Debug, Default)]
+pub struct Memory<W = isize> {
+    dense: Vec<W>,
+    sparse: BTreeMap<usize, W>,
+    // backing value for reads of addresses that have never been written
+    zero: W,
+}
+
+impl<W: Word> Memory<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the value at `addr`, addresses that have never been written read as 0
+    pub fn get(&self, addr: usize) -> W {
+        match self.dense.get(addr) {
+            Some(val) => *val,
+            None => *self.sparse.get(&addr).unwrap_or(&W::ZERO),
+        }
+    }
+
+    /// Write `val` to `addr`, growing memory as needed
+    pub fn set(&mut self, addr: usize, val: W) {
+        *self.cell_mut(addr) = val;
+    }
+
+    /// One past the highest address which has been loaded or written
+    pub fn len(&self) -> usize {
+        match self.sparse.keys().next_back() {
+            Some(last) => last + 1,
+            None => self.dense.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over every non-zero cell as `(address, value)`, in address order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, W)> + '_ {
+        self.non_zero().map(|(addr, val)| (addr, *val))
+    }
+
+    /// The contiguous region of memory starting at address 0.
+    ///
+    /// This holds the loaded program and any nearby writes, but not writes to far off addresses.
+    pub fn as_slice(&self) -> &[W] {
+        &self.dense
+    }
+
+    fn cell_mut(&mut self, addr: usize) -> &mut W {
+        if addr >= self.dense.len() {
+            if addr - self.dense.len() >= MAX_DENSE_GROWTH {
+                return self.sparse.entry(addr).or_insert(W::ZERO);
+            }
+            self.grow(addr + 1);
+        }
+        &mut self.dense[addr]
+    }
+
+    /// Extend the dense region to `new_len` words, pulling in any sparse values it now covers
+    fn grow(&mut self, new_len: usize) {
+        self.dense.resize(new_len, W::ZERO);
+        let beyond = self.sparse.split_off(&new_len);
+        for (addr, val) in std::mem::replace(&mut self.sparse, beyond) {
+            self.dense[addr] = val;
+        }
+    }
+}
+
+/// Only `isize` memory converts from a `Vec`, so that integer literals infer a type; memory of
+/// other word types can be collected or parsed instead
+impl From<Vec<isize>> for Memory {
+    fn from(dense: Vec<isize>) -> Self {
+        Memory {
+            dense,
+            sparse: BTreeMap::new(),
+            zero: 0,
+        }
+    }
+}
+
+impl From<&[isize]> for Memory {
+    fn from(words: &[isize]) -> Self {
+        Memory::from(words.to_vec())
+    }
+}
+
+impl<W: Word> FromIterator<W> for Memory<W> {
+    fn from_iter<I: IntoIterator<Item = W>>(iter: I) -> Self {
+        Memory {
+            dense: iter.into_iter().collect(),
+            sparse: BTreeMap::new(),
+            zero: W::ZERO,
+        }
+    }
+}
+
+/// Parse a program in the puzzle input format, a single line of comma separated integers
+impl<W: Word> FromStr for Memory<W> {
+    type Err = ParseIntError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        line.trim()
+            .split(',')
+            .map(|piece| piece.trim().parse())
+            .collect()
+    }
+}
+
+impl<W: PartialEq> Memory<W> {
+    /// Every cell not holding zero, in address order
+    fn non_zero(&self) -> impl Iterator<Item = (usize, &W)> + '_ {
+        self.dense
+            .iter()
+            .enumerate()
+            .chain(self.sparse.iter().map(|(addr, val)| (*addr, val)))
+            .filter(move |(_, val)| **val != self.zero)
+    }
+}
+
+/// Memories are equal when every address reads the same, however the values are stored
+impl<W: PartialEq> PartialEq for Memory<W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.non_zero().eq(other.non_zero())
+    }
+}
+
+impl<W: Word> Index<usize> for Memory<W> {
+    type Output = W;
+
+    fn index(&self, addr: usize) -> &W {
+        match self.dense.get(addr) {
+            Some(val) => val,
+            None => self.sparse.get(&addr).unwrap_or(&self.zero),
+        }
+    }
+}
+
+impl<W: Word> IndexMut<usize> for Memory<W> {
+    fn index_mut(&mut self, addr: usize) -> &mut W {
+        self.cell_mut(addr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_default_to_zero() {
+        let mem = Memory::from(vec![1, 2, 3]);
+        assert_eq!(mem.get(2), 3);
+        assert_eq!(mem.get(3), 0);
+        assert_eq!(mem[1_000_000_000], 0);
+        // reading must not grow memory
+        assert_eq!(mem.len(), 3);
+    }
+
+    #[test]
+    fn parse_program() {
+        let mem: Memory = "1,0, 0,-3,99\n".parse().unwrap();
+        assert_eq!(mem.as_slice(), &[1, 0, 0, -3, 99]);
+        assert!("1,,2".parse::<Memory>().is_err());
+    }
+
+    #[test]
+    fn nearby_writes_grow_dense() {
+        let mut mem = Memory::from(vec![1, 2, 3]);
+        mem.set(10, 7);
+        assert_eq!(mem.as_slice(), &[1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 7]);
+        mem[11] = 8;
+        assert_eq!(mem.len(), 12);
+        assert_eq!(mem[11], 8);
+    }
+
+    #[test]
+    fn far_writes_are_sparse() {
+        let mut mem = Memory::from(vec![1, 2, 3]);
+        let far = 1 << 40;
+        mem[far] = 5;
+        assert_eq!(mem.as_slice().len(), 3);
+        assert_eq!(mem[far], 5);
+        assert_eq!(mem.len(), far + 1);
+
+        // growing the dense region over a sparse value must keep the value
+        mem.set(MAX_DENSE_GROWTH + 10, 9);
+        mem.set(MAX_DENSE_GROWTH, 4);
+        assert_eq!(mem.as_slice().len(), MAX_DENSE_GROWTH + 1);
+        mem.set(MAX_DENSE_GROWTH + 20, 6);
+        assert_eq!(mem.get(MAX_DENSE_GROWTH + 10), 9);
+        assert_eq!(mem.as_slice()[MAX_DENSE_GROWTH + 10], 9);
+        assert_eq!(mem[far], 5);
+
+        let cells: Vec<(usize, isize)> = mem.iter().collect();
+        assert_eq!(
+            cells,
+            vec![
+                (0, 1),
+                (1, 2),
+                (2, 3),
+                (MAX_DENSE_GROWTH, 4),
+                (MAX_DENSE_GROWTH + 10, 9),
+                (MAX_DENSE_GROWTH + 20, 6),
+                (far, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn equal_by_value() {
+        let mem = Memory::from(vec![1, 2, 3]);
+        // writing a zero, nearby or far off, doesn't change what any address reads
+        let mut near = mem.clone();
+        near.set(10, 0);
+        let mut far = mem.clone();
+        far.set(10_000, 0);
+        assert_eq!(near, mem);
+        assert_eq!(far, mem);
+        assert_eq!(near, far);
+
+        // the same values held densely in one and sparsely in the other
+        let mut sparse = mem.clone();
+        sparse.set(MAX_DENSE_GROWTH + 10, 7);
+        let mut dense = mem.clone();
+        dense.set(MAX_DENSE_GROWTH, 0);
+        dense.set(MAX_DENSE_GROWTH + 10, 7);
+        assert_eq!(dense.as_slice().len(), MAX_DENSE_GROWTH + 11);
+        assert_eq!(sparse, dense);
+        sparse.set(2, 4);
+        assert_ne!(sparse, dense);
+    }
+}
@@ -0,0 +1,834 @@
+//! This module implements an IntCode interpreter.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+
+pub mod amplifier;
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod compile;
+mod coverage;
+pub mod custom;
+pub mod debugger;
+pub mod device;
+pub mod disasm;
+mod error;
+mod history;
+mod limits;
+mod machine;
+mod memory;
+pub mod network;
+mod profile;
+pub mod snapshot;
+pub mod transcript;
+pub mod transpile;
+// output of `transpile` for the program in its tests, kept exactly as generated
+#[cfg(test)]
+#[rustfmt::skip]
+mod transpiled;
+mod word;
+
+pub use coverage::Coverage;
+pub use error::IntcodeError;
+pub use history::{History, Record, Write};
+pub use limits::Limits;
+pub use machine::{Machine, State};
+pub use memory::Memory;
+pub use profile::Profile;
+pub use word::{Checked, Word};
+
+// The following terminology notes are taken from day 2 part 2
+//  - memory: the list of integers used when interpreting
+//  - address/position: the value at a given index into memory
+//  - opcode: mark the beginning of an instruction and denote the instruction
+//  - parameters: the values after an instruction used by the instruction
+//  - instruction pointer: the address of the current instruction
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OpCode {
+    Add = 1,        // *(pc+1) + *(pc+2) => *(pc+3)
+    Multiply = 2,   // *(pc+1) * *(pc+2) => *(pc+3)
+    ReadIn = 3,     // store input to *(pc+1)
+    WriteOut = 4,   // print value of *(pc+1) to output
+    JmpIfTrue = 5,  // jump if *(pc+1) != 0 => ip = *(pc+2)
+    JmpIfFalse = 6, // jump if *(pc+1) == 0 => ip = *(pc+2)
+    LessThan = 7,   // if *(pc+1) < *(pc+2) => *(pc+3) = 1, else 0
+    Equals = 8,     // if *(pc+1) == *(pc+2) => *(pc+3) = 1, else 0
+    AdjustBase = 9, // relative base += *(pc+1)
+    Halt = 99,
+}
+
+impl TryFrom<isize> for OpCode {
+    type Error = &'static str;
+
+    fn try_from(num: isize) -> Result<Self, Self::Error> {
+        match num {
+            1 => Ok(Self::Add),
+            2 => Ok(Self::Multiply),
+            3 => Ok(Self::ReadIn),
+            4 => Ok(Self::WriteOut),
+            5 => Ok(Self::JmpIfTrue),
+            6 => Ok(Self::JmpIfFalse),
+            7 => Ok(Self::LessThan),
+            8 => Ok(Self::Equals),
+            9 => Ok(Self::AdjustBase),
+
+            99 => Ok(Self::Halt),
+
+            _ => Err("invalid opcode value"),
+        }
+    }
+}
+
+impl OpCode {
+    /// Number of parameters following the opcode
+    fn num_params(&self) -> usize {
+        use OpCode::*;
+        match self {
+            Add | Multiply | LessThan | Equals => 3,
+            JmpIfTrue | JmpIfFalse => 2,
+            ReadIn | WriteOut | AdjustBase => 1,
+            Halt => 0,
+        }
+    }
+
+    /// Which parameter, counting from 1, is the destination written to by the instruction
+    fn write_param(&self) -> Option<usize> {
+        use OpCode::*;
+        match self {
+            Add | Multiply | LessThan | Equals => Some(3),
+            ReadIn => Some(1),
+            WriteOut | JmpIfTrue | JmpIfFalse | AdjustBase | Halt => None,
+        }
+    }
+
+    /// Short name used in assembly listings
+    fn mnemonic(&self) -> &'static str {
+        use OpCode::*;
+        match self {
+            Add => "ADD",
+            Multiply => "MUL",
+            ReadIn => "IN",
+            WriteOut => "OUT",
+            JmpIfTrue => "JT",
+            JmpIfFalse => "JF",
+            LessThan => "LT",
+            Equals => "EQ",
+            AdjustBase => "ARB",
+            Halt => "HALT",
+        }
+    }
+
+    /// Look up an opcode by its mnemonic, ignoring case
+    fn from_mnemonic(name: &str) -> Option<OpCode> {
+        (1..=9)
+            .chain(std::iter::once(99))
+            .filter_map(|num| OpCode::try_from(num).ok())
+            .find(|op| op.mnemonic().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AddrMode {
+    Pos = 0,
+    Imm = 1,
+    Rel = 2,
+}
+
+impl TryFrom<isize> for AddrMode {
+    type Error = &'static str;
+
+    fn try_from(num: isize) -> Result<Self, Self::Error> {
+        match num {
+            0 => Ok(Self::Pos),
+            1 => Ok(Self::Imm),
+            2 => Ok(Self::Rel),
+            _ => Err("invalid address mode value"),
+        }
+    }
+}
+
+/// Which part of an instruction word failed to decode
+#[derive(Debug, PartialEq)]
+enum DecodeError {
+    Opcode,
+    Mode(usize), // parameter number, counting from 1
+}
+
+impl DecodeError {
+    fn at(self, ip: usize, word: i128) -> IntcodeError {
+        match self {
+            DecodeError::Opcode => IntcodeError::InvalidOpcode { ip, word },
+            DecodeError::Mode(param) => IntcodeError::InvalidMode { ip, word, param },
+        }
+    }
+}
+
+/// Parse instruction will take a full instruction, and split it into the original instruction
+/// along with addressing modes for each argument.
+///
+/// Words are widened to `i128` by the caller, so this works for any `Word` type.
+fn parse_instruction(word: i128) -> Result<(OpCode, AddrMode, AddrMode, AddrMode), DecodeError> {
+    if word <= 0 {
+        return Err(DecodeError::Opcode);
+    }
+
+    // both of these are small enough to fit any integer type
+    let digit = |place: i128| (word / place % 10) as isize;
+    let mode = |param: usize, place: i128| {
+        AddrMode::try_from(digit(place)).map_err(|_| DecodeError::Mode(param))
+    };
+    let op = (word % 100) as isize;
+    Ok((
+        OpCode::try_from(op).map_err(|_| DecodeError::Opcode)?, // first two digits are op
+        mode(1, 100)?,                                          // 100s place
+        mode(2, 1000)?,                                         // 1000s place
+        mode(3, 10000)?,                                        // 10000s place
+    ))
+}
+
+/// Answer from an input source when polled without blocking
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Poll<W = isize> {
+    Ready(W),
+    /// Nothing available yet, but there may be later
+    Pending,
+    /// Source has run dry and will never produce another value
+    Closed,
+}
+
+/// Trait is used by interpret for reading information interactively
+pub trait Input<W: Word = isize> {
+    fn get_word(&mut self) -> W;
+
+    /// Fetch the next input value, or `None` if the source has run dry
+    fn next_input(&mut self) -> Option<W> {
+        Some(self.get_word())
+    }
+
+    /// Fetch the next input value without blocking, used by `Machine::run_with`
+    fn poll_input(&mut self) -> Poll<W> {
+        match self.next_input() {
+            Some(val) => Poll::Ready(val),
+            None => Poll::Closed,
+        }
+    }
+}
+
+/// Trait is used by `interpret` for writing information interactively
+pub trait Output<W: Word = isize> {
+    fn write_word(&mut self, val: W);
+}
+
+// Implementations for Input trait
+
+impl<W: Word> Input<W> for () {
+    fn get_word(&mut self) -> W {
+        panic!("Program requested input, but input source was ()");
+    }
+
+    fn next_input(&mut self) -> Option<W> {
+        None
+    }
+}
+
+/// A single word is given every time the program asks
+macro_rules! impl_constant_input {
+    ($($t:ty),*) => {$(
+        impl Input<$t> for $t {
+            fn get_word(&mut self) -> $t {
+                *self
+            }
+        }
+    )*};
+}
+
+impl_constant_input!(
+    isize,
+    i64,
+    i128,
+    Checked<isize>,
+    Checked<i64>,
+    Checked<i128>
+);
+
+/// Values are consumed from the front of the queue
+impl<W: Word> Input<W> for VecDeque<W> {
+    fn get_word(&mut self) -> W {
+        self.next_input()
+            .expect("Program requested input, but input queue was empty")
+    }
+
+    fn next_input(&mut self) -> Option<W> {
+        self.pop_front()
+    }
+
+    /// An empty queue may be refilled, so is only pending
+    fn poll_input(&mut self) -> Poll<W> {
+        match self.pop_front() {
+            Some(val) => Poll::Ready(val),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<W: Word> Input<W> for &mut VecDeque<W> {
+    fn get_word(&mut self) -> W {
+        (**self).get_word()
+    }
+
+    fn next_input(&mut self) -> Option<W> {
+        (**self).next_input()
+    }
+
+    fn poll_input(&mut self) -> Poll<W> {
+        (**self).poll_input()
+    }
+}
+
+/// Values are consumed from the start of the slice, which shrinks as it is read
+impl<W: Word> Input<W> for &[W] {
+    fn get_word(&mut self) -> W {
+        self.next_input()
+            .expect("Program requested input, but input slice was empty")
+    }
+
+    fn next_input(&mut self) -> Option<W> {
+        let (first, rest) = self.split_first()?;
+        *self = rest;
+        Some(*first)
+    }
+}
+
+/// Adapter allowing any iterator of values to be used as input
+pub struct IterInput<I>(pub I);
+
+impl<W: Word, I: Iterator<Item = W>> Input<W> for IterInput<I> {
+    fn get_word(&mut self) -> W {
+        self.next_input()
+            .expect("Program requested input, but input iterator was exhausted")
+    }
+
+    fn next_input(&mut self) -> Option<W> {
+        self.0.next()
+    }
+}
+
+/// The closure is called each time the program wants a value
+impl<W: Word, F: FnMut() -> W> Input<W> for F {
+    fn get_word(&mut self) -> W {
+        self()
+    }
+}
+
+/// Blocks until a value arrives, the input runs dry once every sender has hung up. Polling never
+/// blocks.
+impl<W: Word> Input<W> for Receiver<W> {
+    fn get_word(&mut self) -> W {
+        self.next_input()
+            .expect("Program requested input, but input channel was closed")
+    }
+
+    fn next_input(&mut self) -> Option<W> {
+        self.recv().ok()
+    }
+
+    fn poll_input(&mut self) -> Poll<W> {
+        match self.try_recv() {
+            Ok(val) => Poll::Ready(val),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(TryRecvError::Disconnected) => Poll::Closed,
+        }
+    }
+}
+
+// Implementations for Output trait
+
+impl<W: Word> Output<W> for () {
+    fn write_word(&mut self, _val: W) {
+        panic!("Program attempted to write value, but out was ()");
+    }
+}
+
+impl<W: Word> Output<W> for &mut Vec<W> {
+    fn write_word(&mut self, val: W) {
+        self.push(val)
+    }
+}
+
+impl<W: Word> Output<W> for &mut VecDeque<W> {
+    fn write_word(&mut self, val: W) {
+        self.push_back(val)
+    }
+}
+
+/// The closure is called with each value the program writes
+impl<W: Word, F: FnMut(W)> Output<W> for F {
+    fn write_word(&mut self, val: W) {
+        self(val)
+    }
+}
+
+impl<W: Word> Output<W> for Sender<W> {
+    fn write_word(&mut self, val: W) {
+        self.send(val)
+            .expect("Program attempted to write value, but output channel was closed");
+    }
+}
+
+/// Interpret array as an IntCode program.
+///
+/// `mem` is the initial machine memory state, it is modified during the run and grows as the
+/// program writes beyond its end
+///
+/// Returns the final value at address 0, or the first error encountered. Memory is left as it
+/// was at the point execution stopped either way.
+pub fn interpret<W: Word>(
+    mem: &mut Memory<W>,
+    input: impl Input<W>,
+    output: impl Output<W>,
+) -> Result<W, IntcodeError> {
+    interpret_with_limits(mem, input, output, Limits::default())
+}
+
+/// Same as `interpret`, but fails instead of running forever according to `limits`
+pub fn interpret_with_limits<W: Word>(
+    mem: &mut Memory<W>,
+    input: impl Input<W>,
+    output: impl Output<W>,
+    limits: Limits,
+) -> Result<W, IntcodeError> {
+    let mut machine = Machine::from_memory(std::mem::take(mem));
+    machine.set_limits(limits);
+    drive(machine, mem, input, output)
+}
+
+/// Run `machine` to completion for `interpret`, leaving its final memory in `mem`
+fn drive<W: Word>(
+    mut machine: Machine<W>,
+    mem: &mut Memory<W>,
+    mut input: impl Input<W>,
+    mut output: impl Output<W>,
+) -> Result<W, IntcodeError> {
+    let result = loop {
+        match machine.run() {
+            Ok(State::NeedsInput) => match input.next_input() {
+                Some(val) => machine.push_input(val),
+                None => {
+                    let ip = machine.ip();
+                    let word = machine.memory()[ip].to_i128();
+                    break Err(IntcodeError::InputExhausted { ip, word });
+                }
+            },
+            Ok(State::Output(val)) => output.write_word(val),
+            Ok(State::Halted) => break Ok(()),
+            Err(err) => break Err(err),
+        }
+    };
+
+    *mem = machine.into_memory();
+    result.map(|_| mem[0])
+}
+
+/// The day 5 diagnostic program, shared by the tests which run a real program
+#[cfg(test)]
+fn day05() -> Vec<isize> {
+    include_str!("../../../inputs/day05.txt")
+        .trim()
+        .split(',')
+        .map(|word| word.parse().unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interpret_day2_examples() {
+        let mut programs = [
+            Memory::from(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]),
+            Memory::from(vec![1, 0, 0, 0, 99]),
+            Memory::from(vec![2, 3, 0, 3, 99]),
+            Memory::from(vec![2, 4, 4, 5, 99, 0]),
+            Memory::from(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]),
+        ];
+        let outputs = [
+            vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
+            vec![2, 0, 0, 0, 99],
+            vec![2, 3, 0, 6, 99],
+            vec![2, 4, 4, 5, 99, 9801],
+            vec![30, 1, 1, 4, 2, 5, 6, 0, 99],
+        ];
+        for (program, output) in programs.iter_mut().zip(outputs.iter()) {
+            assert_eq!(interpret(program, (), ()), Ok(output[0]));
+            assert_eq!(program.as_slice(), &output[..]);
+        }
+    }
+
+    #[test]
+    fn test_parse_instruction() {
+        use AddrMode::*;
+        use OpCode::*;
+
+        type Output = (OpCode, AddrMode, AddrMode, AddrMode);
+        fn eq(left: Output, right: Output) -> bool {
+            left.0 == right.0 && left.1 == right.1 && left.2 == right.2 && left.3 == right.3
+        }
+
+        // from day 5 examples
+        assert!(eq(
+            parse_instruction(1002).unwrap(),
+            (Multiply, Pos, Imm, Pos)
+        ));
+
+        // synthetic
+        assert!(eq(parse_instruction(2).unwrap(), (Multiply, Pos, Pos, Pos)));
+        assert!(eq(parse_instruction(11101).unwrap(), (Add, Imm, Imm, Imm)));
+        assert!(eq(parse_instruction(10101).unwrap(), (Add, Imm, Pos, Imm)));
+        assert!(eq(
+            parse_instruction(104).unwrap(),
+            (WriteOut, Imm, Pos, Pos)
+        ));
+        assert!(eq(
+            parse_instruction(10003).unwrap(),
+            (ReadIn, Pos, Pos, Imm)
+        ));
+    }
+
+    #[test]
+    fn day5_snippets() {
+        // This tests immediate and positional addressing and negative immediate support
+        // Should: find (100 + -1), store result @4
+        let mut simple_prog = Memory::from(vec![1101, 100, -1, 4, 0]);
+        interpret(&mut simple_prog, (), ()).unwrap();
+        assert_eq!(simple_prog[4], 99);
+
+        // This should save whatever it gets from input to @0, then print it back out
+        let arb_input = 10346;
+        let mut output = Vec::new();
+        let mut simple_io = Memory::from(vec![3, 0, 4, 0, 99]);
+        interpret(&mut simple_io, arb_input, &mut output).unwrap();
+        println!("{:?}", output[0]);
+        println!("{:?}", simple_io);
+        assert_eq!(simple_io[0], arb_input);
+        assert_eq!(output[0], arb_input);
+    }
+
+    #[test]
+    fn day5_jump_tests() {
+        // These programs compare the input to 8, outputting 1 if eq or lt, 0 otherwise
+        // they use different methods for each
+        // test eq
+        let progs_eq_to_eight = [
+            vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8], // positional
+            vec![3, 3, 1108, -1, 8, 3, 4, 3, 99],     // immediate
+        ];
+        for (input, exp_out) in [(0, 0), (8, 1), (-8, 0), (10, 0)] {
+            for prog in progs_eq_to_eight.iter() {
+                let mut prog = Memory::from(prog.clone());
+                let mut output = Vec::new();
+                interpret(&mut prog, input, &mut output).unwrap();
+                assert_eq!(exp_out, output[0]);
+            }
+        }
+        // test lt
+        let progs_lt_eight = [
+            vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8], // lt positional
+            vec![3, 3, 1107, -1, 8, 3, 4, 3, 99],     // lt immediate
+        ];
+        for (input, exp_out) in [(0, 1), (-1, 1), (8, 0), (10, 0)] {
+            for prog in progs_lt_eight.iter() {
+                let mut prog = Memory::from(prog.clone());
+                let mut output = Vec::new();
+                interpret(&mut prog, input, &mut output).unwrap();
+                assert_eq!(exp_out, output[0], "input: {}", input);
+            }
+        }
+        // test jump
+        let jump_progs = [
+            vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9], // positional
+            vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],         // immediate
+        ];
+        for (input, exp_out) in [(0, 0), (-1, 1), (8, 1), (10, 1)] {
+            for prog in jump_progs.iter() {
+                let mut prog = Memory::from(prog.clone());
+                let mut output = Vec::new();
+                interpret(&mut prog, input, &mut output).unwrap();
+                assert_eq!(exp_out, output[0], "input: {}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn day5_large_test() {
+        let jmp_prog = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        let in_outs = vec![
+            (-1, 999),
+            (0, 999),
+            (5, 999),
+            (8, 1000),
+            (9, 1001),
+            (14240, 1001),
+        ];
+        let mut output = Vec::new();
+        for (input, exp_out) in in_outs.into_iter() {
+            println!("{:?}", input);
+            let mut prog = Memory::from(jmp_prog.clone());
+            output.clear();
+            interpret(&mut prog, input, &mut output).unwrap();
+            assert_eq!(exp_out, output[0], "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn day9_relative_base() {
+        // Takes no input and produces a copy of itself as output, writing past its own end
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut prog = Memory::from(quine.clone());
+        let mut output = Vec::new();
+        interpret(&mut prog, (), &mut output).unwrap();
+        assert_eq!(output, quine);
+
+        // Should output a 16 digit number
+        let mut prog = Memory::from(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]);
+        let mut output = Vec::new();
+        interpret(&mut prog, (), &mut output).unwrap();
+        assert_eq!(output, vec![1219070632396864]);
+
+        // Should output the large number in the middle
+        let mut prog = Memory::from(vec![104, 1125899906842624, 99]);
+        let mut output = Vec::new();
+        interpret(&mut prog, (), &mut output).unwrap();
+        assert_eq!(output, vec![1125899906842624]);
+    }
+
+    #[test]
+    fn writes_past_end_of_program() {
+        // Stores 5 far beyond the end of the program, then reads it back and outputs it
+        let mut prog = Memory::from(vec![
+            1101,
+            2,
+            3,
+            1_000_000_000_000,
+            4,
+            1_000_000_000_000,
+            99,
+        ]);
+        let mut output = Vec::new();
+        interpret(&mut prog, (), &mut output).unwrap();
+        assert_eq!(output, vec![5]);
+        assert_eq!(prog[1_000_000_000_000], 5);
+        assert_eq!(prog.as_slice().len(), 7);
+    }
+
+    #[test]
+    fn relative_mode_read_write() {
+        // Moves the relative base to 10, reads input to rb-1, then adds rb-1 to itself into rb+0
+        let mut prog = Memory::from(vec![109, 10, 203, -1, 22201, -1, -1, 0, 99, 0, 0]);
+        interpret(&mut prog, 21, ()).unwrap();
+        assert_eq!(prog[9], 21);
+        assert_eq!(prog[10], 42);
+    }
+
+    #[test]
+    fn input_sources() {
+        // adds two inputs and outputs the result
+        fn add_two(input: impl Input) -> Result<isize, IntcodeError> {
+            let prog = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+            let mut output = Vec::new();
+            interpret(&mut Memory::from(prog), input, &mut output).map(|_| output[0])
+        }
+
+        let mut queue: VecDeque<isize> = vec![3, 4].into();
+        assert_eq!(add_two(&mut queue), Ok(7));
+        assert!(queue.is_empty());
+        assert_eq!(add_two(VecDeque::from(vec![1, 2])), Ok(3));
+        let slice: &[isize] = &[5, 6, 7];
+        assert_eq!(add_two(slice), Ok(11));
+        assert_eq!(add_two(IterInput((1..).map(|x| x * 10))), Ok(30));
+        let mut next = 0;
+        let counter = move || {
+            next += 1;
+            next
+        };
+        assert_eq!(add_two(counter), Ok(3));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        tx.send(8).unwrap();
+        tx.send(9).unwrap();
+        drop(tx);
+        assert_eq!(add_two(rx), Ok(17));
+
+        // running dry is reported rather than blocking or panicking
+        let (tx, rx) = std::sync::mpsc::channel();
+        tx.send(1).unwrap();
+        drop(tx);
+        assert_eq!(
+            add_two(rx),
+            Err(IntcodeError::InputExhausted { ip: 2, word: 3 })
+        );
+        assert_eq!(
+            add_two(VecDeque::new()),
+            Err(IntcodeError::InputExhausted { ip: 0, word: 3 })
+        );
+    }
+
+    #[test]
+    fn polling_inputs() {
+        let mut queue: VecDeque<isize> = vec![1].into();
+        assert_eq!(queue.poll_input(), Poll::Ready(1));
+        assert_eq!(queue.poll_input(), Poll::Pending);
+        assert_eq!((&[2isize][..]).poll_input(), Poll::Ready(2));
+        assert_eq!((&[][..]).poll_input(), Poll::<isize>::Closed);
+        assert_eq!(().poll_input(), Poll::<isize>::Closed);
+        assert_eq!(4isize.poll_input(), Poll::Ready(4));
+        assert_eq!(Checked(5i64).poll_input(), Poll::Ready(Checked(5)));
+
+        let (tx, mut rx) = std::sync::mpsc::channel::<isize>();
+        assert_eq!(rx.poll_input(), Poll::Pending);
+        tx.send(3).unwrap();
+        assert_eq!(rx.poll_input(), Poll::Ready(3));
+        drop(tx);
+        assert_eq!(rx.poll_input(), Poll::Closed);
+    }
+
+    #[test]
+    fn output_sinks() {
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+
+        let mut queue = VecDeque::new();
+        interpret(&mut Memory::from(quine.clone()), (), &mut queue).unwrap();
+        assert_eq!(queue, quine);
+
+        let mut sum = 0;
+        interpret(&mut Memory::from(quine.clone()), (), |val| sum += val).unwrap();
+        assert_eq!(sum, quine.iter().sum::<isize>());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        interpret(&mut Memory::from(quine.clone()), (), tx).unwrap();
+        assert_eq!(rx.iter().collect::<Vec<_>>(), quine);
+    }
+
+    #[test]
+    fn reports_errors() {
+        use IntcodeError::*;
+
+        let run = |prog: Vec<isize>| interpret(&mut Memory::from(prog), (), ());
+        assert_eq!(
+            run(vec![1, 0, 0, 0, 42]),
+            Err(InvalidOpcode { ip: 4, word: 42 })
+        );
+        assert_eq!(run(vec![0]), Err(InvalidOpcode { ip: 0, word: 0 }));
+        assert_eq!(
+            run(vec![3001, 0, 0, 0, 99]),
+            Err(InvalidMode {
+                ip: 0,
+                word: 3001,
+                param: 2
+            })
+        );
+        assert_eq!(
+            run(vec![1, -3, 0, 0, 99]),
+            Err(NegativeAddress {
+                ip: 0,
+                word: 1,
+                addr: -3
+            })
+        );
+        assert_eq!(
+            run(vec![1105, 1, -7, 99]),
+            Err(NegativeAddress {
+                ip: 0,
+                word: 1105,
+                addr: -7
+            })
+        );
+        assert_eq!(
+            run(vec![10001, 0, 0, 0, 99]),
+            Err(ImmediateWrite {
+                ip: 0,
+                word: 10001,
+                param: 3
+            })
+        );
+        assert_eq!(
+            run(vec![109, isize::MAX, 204, 1, 99]),
+            Err(OutOfBounds { ip: 2, word: 204 })
+        );
+        assert_eq!(run(vec![3, 0, 99]), Err(InputExhausted { ip: 0, word: 3 }));
+    }
+
+    #[test]
+    fn limits_stop_runaway_programs() {
+        let limits = Limits {
+            max_steps: Some(1000),
+            detect_loops: false,
+        };
+        // reads input, then jumps back to the jump forever
+        let mut prog = Memory::from(vec![3, 0, 1105, 1, 2]);
+        assert_eq!(
+            interpret_with_limits(&mut prog, 5, (), limits),
+            Err(IntcodeError::StepLimit {
+                ip: 2,
+                word: 1105,
+                limit: 1000
+            })
+        );
+        let limits = Limits {
+            max_steps: None,
+            detect_loops: true,
+        };
+        let mut prog = Memory::from(vec![3, 0, 1105, 1, 2]);
+        assert_eq!(
+            interpret_with_limits(&mut prog, 5, (), limits),
+            Err(IntcodeError::InfiniteLoop { ip: 2, word: 1105 })
+        );
+    }
+
+    #[test]
+    fn memory_kept_after_error() {
+        // Stores 7 at @5, then hits an invalid opcode
+        let mut prog = Memory::from(vec![1101, 3, 4, 5, 42, 0]);
+        assert!(interpret(&mut prog, (), ()).is_err());
+        assert_eq!(prog[5], 7);
+    }
+
+    #[test]
+    fn word_types() {
+        // squares 2^40, which is too big for 64 bits but fine in 128
+        let program = "1102,1099511627776,1099511627776,7,4,7,99,0";
+        let mut out = Vec::new();
+        let mut mem: Memory<i128> = program.parse().unwrap();
+        interpret(&mut mem, (), |val| out.push(val)).unwrap();
+        assert_eq!(out, vec![1 << 80]);
+
+        let mut out = Vec::new();
+        let mut mem: Memory<i64> = program.parse().unwrap();
+        interpret(&mut mem, (), |val| out.push(val)).unwrap();
+        assert_eq!(out, vec![0]);
+
+        let mut mem: Memory<Checked<i64>> = program.parse().unwrap();
+        assert_eq!(
+            interpret(&mut mem, (), ()),
+            Err(IntcodeError::Overflow { ip: 0, word: 1102 })
+        );
+
+        // reads and writes through the relative base
+        let mut mem: Memory<Checked<i64>> = "109,20,203,-13,204,-13,99".parse().unwrap();
+        let mut out = Vec::new();
+        interpret(&mut mem, Checked(-8), &mut out).unwrap();
+        assert_eq!(out, vec![Checked(-8)]);
+        assert_eq!(mem[7], Checked(-8));
+    }
+}
@@ -0,0 +1,487 @@
+//! Network of IntCode machines exchanging packets (day 23).
+//!
+//! Every machine is given its network address as its first input. A machine sends a packet by
+//! writing three values: destination address, X and Y. Packets are queued at the destination and
+//! read back as X then Y, a machine reading from an empty queue gets -1.
+//!
+//! Address 255 is the NAT. It remembers the last packet sent to it, and once the network is idle
+//! (every queue empty and no machine sending) delivers that packet to address 0 to wake it up.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::{IntcodeError, Machine, State};
+
+/// Address of the NAT
+pub const NAT: isize = 255;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Packet {
+    pub x: isize,
+    pub y: isize,
+}
+
+/// When the simulation should stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Until {
+    /// First packet sent to the NAT
+    FirstNatPacket,
+    /// First packet the NAT delivers to address 0 with the same Y value twice in a row
+    RepeatedWakeup,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetError {
+    /// Machine at address `machine` failed
+    Intcode { machine: usize, err: IntcodeError },
+    /// Machine at address `from` sent a packet to an address which doesn't exist
+    BadAddress { from: usize, dest: isize },
+    /// Network went idle with nothing for the NAT to send, or every machine halted
+    Stalled,
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetError::Intcode { machine, err } => write!(f, "machine {} failed: {}", machine, err),
+            NetError::BadAddress { from, dest } => {
+                write!(
+                    f,
+                    "machine {} sent a packet to unknown address {}",
+                    from, dest
+                )
+            }
+            NetError::Stalled => write!(f, "network stalled"),
+        }
+    }
+}
+
+impl Error for NetError {}
+
+/// Tracks the NAT's stored packet and what it has delivered
+#[derive(Default)]
+struct Nat {
+    stored: Option<Packet>,
+    last_delivered: Option<Packet>,
+}
+
+impl Nat {
+    /// Called once the network is idle, returns the packet to deliver to address 0, or the final
+    /// answer if the same Y value is being delivered twice in a row
+    fn wake(&mut self) -> Result<Result<Packet, Packet>, NetError> {
+        let packet = self.stored.ok_or(NetError::Stalled)?;
+        if self.last_delivered.map(|last| last.y) == Some(packet.y) {
+            return Ok(Err(packet));
+        }
+        self.last_delivered = Some(packet);
+        Ok(Ok(packet))
+    }
+}
+
+/// Deterministic, single threaded network simulation.
+///
+/// Machines take turns in address order. On its turn a machine is given everything in its queue,
+/// or -1 if the queue is empty, and runs until it wants more input.
+pub struct Network {
+    machines: Vec<Machine>,
+    queues: Vec<VecDeque<isize>>,
+    // partially written packets
+    sending: Vec<Vec<isize>>,
+    halted: Vec<bool>,
+    nat: Nat,
+}
+
+impl Network {
+    pub fn new(program: &[isize], size: usize) -> Self {
+        let machines = (0..size)
+            .map(|addr| {
+                let mut machine = Machine::new(program);
+                machine.push_input(addr as isize);
+                machine
+            })
+            .collect();
+        Network {
+            machines,
+            queues: vec![VecDeque::new(); size],
+            sending: vec![Vec::new(); size],
+            halted: vec![false; size],
+            nat: Nat::default(),
+        }
+    }
+
+    pub fn run(&mut self, until: Until) -> Result<Packet, NetError> {
+        loop {
+            let mut idle = true;
+            for addr in 0..self.machines.len() {
+                if self.halted[addr] {
+                    continue;
+                }
+                match self.queues[addr].is_empty() {
+                    true => self.machines[addr].push_input(-1),
+                    false => {
+                        idle = false;
+                        for val in self.queues[addr].drain(..) {
+                            self.machines[addr].push_input(val);
+                        }
+                    }
+                }
+
+                loop {
+                    let state = self.machines[addr]
+                        .run()
+                        .map_err(|err| NetError::Intcode { machine: addr, err })?;
+                    match state {
+                        State::NeedsInput => break,
+                        State::Halted => {
+                            self.halted[addr] = true;
+                            break;
+                        }
+                        State::Output(val) => {
+                            self.sending[addr].push(val);
+                            if self.sending[addr].len() == 3 {
+                                idle = false;
+                                let (dest, x, y) = match self.sending[addr][..] {
+                                    [dest, x, y] => (dest, x, y),
+                                    _ => unreachable!(),
+                                };
+                                self.sending[addr].clear();
+                                if self.send(addr, dest, Packet { x, y })?
+                                    && until == Until::FirstNatPacket
+                                {
+                                    return Ok(Packet { x, y });
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            if self.halted.iter().all(|h| *h) {
+                return Err(NetError::Stalled);
+            }
+            if idle {
+                match self.nat.wake()? {
+                    Ok(packet) => self.queues[0].extend(&[packet.x, packet.y]),
+                    Err(repeated) => return Ok(repeated),
+                }
+            }
+        }
+    }
+
+    /// Route a packet, returning whether it went to the NAT
+    fn send(&mut self, from: usize, dest: isize, packet: Packet) -> Result<bool, NetError> {
+        if dest == NAT {
+            self.nat.stored = Some(packet);
+            return Ok(true);
+        }
+        match self.queues.get_mut(dest as usize) {
+            Some(queue) if dest >= 0 => {
+                queue.extend(&[packet.x, packet.y]);
+                Ok(false)
+            }
+            _ => Err(NetError::BadAddress { from, dest }),
+        }
+    }
+}
+
+/// Messages from machine threads to the router
+enum Event {
+    Packet {
+        from: usize,
+        dest: isize,
+        packet: Packet,
+    },
+    /// Machine started reading -1, having received `received` values in total so far
+    Idle {
+        from: usize,
+        received: usize,
+    },
+    Halted {
+        from: usize,
+    },
+    Failed {
+        from: usize,
+        err: IntcodeError,
+    },
+}
+
+/// Consecutive reads of -1, with nothing sent in between, after which a machine reports itself
+/// idle
+const IDLE_READS: usize = 2;
+
+/// How long a machine waits for a packet before it is given -1 instead
+const IDLE_WAIT: Duration = Duration::from_millis(5);
+
+/// Run the network with one thread per machine, connected to a router by channels.
+///
+/// Thread scheduling makes the order packets arrive in nondeterministic, so this only gives the
+/// same answers as `Network::run` for programs which don't depend on that order.
+pub fn run_threaded(program: &[isize], size: usize, until: Until) -> Result<Packet, NetError> {
+    let (event_tx, events) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut inputs = Vec::new();
+    let mut handles = Vec::new();
+    for addr in 0..size {
+        let (input_tx, input) = mpsc::channel::<isize>();
+        inputs.push(input_tx);
+        let event_tx = event_tx.clone();
+        let stop = Arc::clone(&stop);
+        let mut machine = Machine::new(program);
+        machine.push_input(addr as isize);
+        handles.push(thread::spawn(move || {
+            let mut received = 0;
+            let mut sending = Vec::new();
+            // reads of -1 since the machine last received or sent anything
+            let mut empty_reads = 0;
+            loop {
+                let state = match run_until_stopped(&mut machine, &stop) {
+                    Some(state) => state,
+                    None => return,
+                };
+                let event = match state {
+                    Ok(State::NeedsInput) => match input.recv_timeout(IDLE_WAIT) {
+                        Ok(val) => {
+                            received += 1;
+                            empty_reads = 0;
+                            machine.push_input(val);
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Timeout) => {
+                            machine.push_input(-1);
+                            empty_reads += 1;
+                            // only report going idle, not every read while it stays that way
+                            if empty_reads != IDLE_READS {
+                                continue;
+                            }
+                            Event::Idle {
+                                from: addr,
+                                received,
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    },
+                    Ok(State::Output(val)) => {
+                        empty_reads = 0;
+                        sending.push(val);
+                        if sending.len() < 3 {
+                            continue;
+                        }
+                        let packet = Packet {
+                            x: sending[1],
+                            y: sending[2],
+                        };
+                        let dest = sending[0];
+                        sending.clear();
+                        Event::Packet {
+                            from: addr,
+                            dest,
+                            packet,
+                        }
+                    }
+                    Ok(State::Halted) => Event::Halted { from: addr },
+                    Err(err) => Event::Failed { from: addr, err },
+                };
+                let finished = matches!(event, Event::Halted { .. } | Event::Failed { .. });
+                if event_tx.send(event).is_err() || finished {
+                    return;
+                }
+            }
+        }));
+    }
+    drop(event_tx);
+
+    let result = route(&events, &inputs, until);
+    // hanging up makes every waiting machine thread exit, the flag stops the running ones
+    stop.store(true, Ordering::Relaxed);
+    drop(inputs);
+    drop(events);
+    for handle in handles {
+        handle.join().expect("Network machine thread panicked");
+    }
+    result
+}
+
+/// Run `machine` as `Machine::run` does, giving up with `None` once `stop` is set
+fn run_until_stopped(
+    machine: &mut Machine,
+    stop: &AtomicBool,
+) -> Option<Result<State, IntcodeError>> {
+    while !stop.load(Ordering::Relaxed) {
+        if let Some(result) = machine.step().transpose() {
+            return Some(result);
+        }
+    }
+    None
+}
+
+fn route(
+    events: &mpsc::Receiver<Event>,
+    inputs: &[mpsc::Sender<isize>],
+    until: Until,
+) -> Result<Packet, NetError> {
+    let size = inputs.len();
+    let mut sent = vec![0; size];
+    let mut idle = vec![false; size];
+    let mut halted = vec![false; size];
+    let mut nat = Nat::default();
+
+    let deliver = |sent: &mut Vec<usize>, dest: usize, packet: Packet| {
+        // a machine which has exited can't be reached, which is fine to ignore
+        let _ = inputs[dest].send(packet.x);
+        let _ = inputs[dest].send(packet.y);
+        sent[dest] += 2;
+    };
+
+    for event in events.iter() {
+        match event {
+            Event::Packet { from, dest, packet } => {
+                idle[from] = false;
+                if dest == NAT {
+                    nat.stored = Some(packet);
+                    if until == Until::FirstNatPacket {
+                        return Ok(packet);
+                    }
+                } else if dest >= 0 && (dest as usize) < size {
+                    idle[dest as usize] = false;
+                    deliver(&mut sent, dest as usize, packet);
+                } else {
+                    return Err(NetError::BadAddress { from, dest });
+                }
+            }
+            Event::Idle { from, received } => {
+                // stale if it was sent before the machine saw everything routed to it
+                idle[from] = received == sent[from];
+            }
+            Event::Halted { from } => halted[from] = true,
+            Event::Failed { from, err } => return Err(NetError::Intcode { machine: from, err }),
+        }
+
+        if halted.iter().all(|h| *h) {
+            return Err(NetError::Stalled);
+        }
+        if (0..size).all(|addr| halted[addr] || idle[addr]) {
+            match nat.wake()? {
+                Ok(packet) => {
+                    // the rest stay idle, they only change once something is sent to them
+                    idle[0] = false;
+                    deliver(&mut sent, 0, packet);
+                }
+                Err(repeated) => return Ok(repeated),
+            }
+        }
+    }
+    Err(NetError::Stalled)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::asm::assemble;
+    use super::*;
+
+    /// Machine 0 sends (7, 42) to machine 1. Every machine adds its address to X of each packet it
+    /// receives and forwards it to the next address, the last machine forwards to the NAT.
+    fn relay(size: usize) -> Vec<isize> {
+        assemble(&format!(
+            "
+                    IN [addr]
+                    JT [addr], #recv
+                    OUT #1
+                    OUT #7
+                    OUT #42
+            recv:   IN [x]
+                    EQ [x], #-1, [tmp]
+                    JT [tmp], #recv
+                    IN [y]
+                    ADD [x], [addr], [x]
+                    ADD [addr], #1, [dest]
+                    EQ [dest], #{}, [tmp]
+                    JF [tmp], #send
+                    ADD #255, #0, [dest]
+            send:   OUT [dest]
+                    OUT [x]
+                    OUT [y]
+                    JT #1, #recv
+            addr:   .data 0
+            x:      .data 0
+            y:      .data 0
+            dest:   .data 0
+            tmp:    .data 0
+            ",
+            size
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn single_threaded() {
+        let program = relay(5);
+        let mut network = Network::new(&program, 5);
+        assert_eq!(
+            network.run(Until::FirstNatPacket),
+            Ok(Packet { x: 17, y: 42 })
+        );
+        // X keeps growing each time the NAT restarts the relay, Y doesn't
+        let mut network = Network::new(&program, 5);
+        assert_eq!(
+            network.run(Until::RepeatedWakeup),
+            Ok(Packet { x: 27, y: 42 })
+        );
+    }
+
+    #[test]
+    fn threaded() {
+        let program = relay(5);
+        assert_eq!(
+            run_threaded(&program, 5, Until::FirstNatPacket),
+            Ok(Packet { x: 17, y: 42 })
+        );
+        assert_eq!(
+            run_threaded(&program, 5, Until::RepeatedWakeup),
+            Ok(Packet { x: 27, y: 42 })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        // every machine sends to address 9, which doesn't exist
+        let program = vec![104, 9, 104, 0, 104, 0, 99];
+        assert_eq!(
+            Network::new(&program, 2).run(Until::FirstNatPacket),
+            Err(NetError::BadAddress { from: 0, dest: 9 })
+        );
+        // quietly reads forever, so the NAT has nothing to send
+        let program = vec![3, 5, 1105, 1, 0, 0];
+        assert_eq!(
+            Network::new(&program, 2).run(Until::RepeatedWakeup),
+            Err(NetError::Stalled)
+        );
+        assert_eq!(
+            run_threaded(&program, 2, Until::RepeatedWakeup),
+            Err(NetError::Stalled)
+        );
+
+        // machine 0 sends to the NAT, then every machine spins without reading or halting
+        let program = assemble(
+            "
+                    IN [addr]
+                    JT [addr], #spin
+                    OUT #255
+                    OUT #3
+                    OUT #4
+            spin:   JT #1, #spin
+            addr:   .data 0
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            run_threaded(&program, 3, Until::FirstNatPacket),
+            Ok(Packet { x: 3, y: 4 })
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Execution statistics collected by a `Machine` with profiling turned on.
+//!
+//! Counts are kept per opcode, per parameter addressing mode and per instruction address, along
+//! with the number of input and output instructions. Profiles from several runs can be merged,
+//! e.g. to see where a whole brute force search spends its time.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use super::{disasm, AddrMode, OpCode};
+
+const MODE_NAMES: [&str; 3] = ["position", "immediate", "relative"];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    opcodes: BTreeMap<&'static str, usize>,
+    modes: [usize; 3],
+    addresses: BTreeMap<usize, usize>,
+    steps: usize,
+    inputs: usize,
+    outputs: usize,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one executed instruction
+    pub(super) fn record(&mut self, ip: usize, op: OpCode, modes: [AddrMode; 3]) {
+        self.count(ip, op.mnemonic(), &modes[..op.num_params()]);
+        match op {
+            OpCode::ReadIn => self.inputs += 1,
+            OpCode::WriteOut => self.outputs += 1,
+            _ => (),
+        }
+    }
+
+    /// Count one executed instruction with an opcode from a `custom::Registry`
+    pub(super) fn record_custom(
+        &mut self,
+        ip: usize,
+        mnemonic: &'static str,
+        modes: &[AddrMode],
+        output: bool,
+    ) {
+        self.count(ip, mnemonic, modes);
+        if output {
+            self.outputs += 1;
+        }
+    }
+
+    fn count(&mut self, ip: usize, mnemonic: &'static str, modes: &[AddrMode]) {
+        *self.opcodes.entry(mnemonic).or_insert(0) += 1;
+        for mode in modes {
+            self.modes[*mode as usize] += 1;
+        }
+        *self.addresses.entry(ip).or_insert(0) += 1;
+        self.steps += 1;
+    }
+
+    /// Add the counts from `other` into this profile
+    pub fn merge(&mut self, other: &Profile) {
+        for (op, count) in other.opcodes.iter() {
+            *self.opcodes.entry(op).or_insert(0) += count;
+        }
+        for (mine, theirs) in self.modes.iter_mut().zip(other.modes.iter()) {
+            *mine += theirs;
+        }
+        for (addr, count) in other.addresses.iter() {
+            *self.addresses.entry(*addr).or_insert(0) += count;
+        }
+        self.steps += other.steps;
+        self.inputs += other.inputs;
+        self.outputs += other.outputs;
+    }
+
+    /// Total instructions executed
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Number of input instructions executed
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// Number of output instructions executed
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    /// Executions of each opcode by mnemonic, most frequent first
+    pub fn opcode_counts(&self) -> Vec<(&'static str, usize)> {
+        let mut counts: Vec<(&'static str, usize)> = self
+            .opcodes
+            .iter()
+            .map(|(op, count)| (*op, *count))
+            .collect();
+        counts.sort_by_key(|(_, count)| Reverse(*count));
+        counts
+    }
+
+    /// Parameters executed in each addressing mode
+    pub fn mode_counts(&self) -> [(&'static str, usize); 3] {
+        [
+            (MODE_NAMES[0], self.modes[0]),
+            (MODE_NAMES[1], self.modes[1]),
+            (MODE_NAMES[2], self.modes[2]),
+        ]
+    }
+
+    /// Times the instruction at `addr` was executed
+    pub fn count_at(&self, addr: usize) -> usize {
+        self.addresses.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// The `n` most executed instruction addresses as `(address, count)`, busiest first
+    pub fn hot_spots(&self, n: usize) -> Vec<(usize, usize)> {
+        let mut spots: Vec<(usize, usize)> = self
+            .addresses
+            .iter()
+            .map(|(addr, count)| (*addr, *count))
+            .collect();
+        // stable sort keeps equally busy addresses in address order
+        spots.sort_by_key(|(_, count)| Reverse(*count));
+        spots.truncate(n);
+        spots
+    }
+
+    /// Summary of the counts, listing the `top` busiest instructions as disassembled from `mem`
+    pub fn report(&self, mem: &[isize], top: usize) -> String {
+        let percent = |count: usize| 100.0 * count as f64 / self.steps.max(1) as f64;
+        let mut out = format!(
+            "steps: {}  inputs: {}  outputs: {}\n\nopcode        count       %\n",
+            self.steps, self.inputs, self.outputs
+        );
+        for (op, count) in self.opcode_counts() {
+            out += &format!("{:<6} {:>12} {:>6.1}%\n", op, count, percent(count));
+        }
+
+        let params: usize = self.modes.iter().sum();
+        out += "\nmode          count       %\n";
+        for (mode, count) in self.mode_counts().iter() {
+            let share = 100.0 * *count as f64 / params.max(1) as f64;
+            out += &format!("{:<9} {:>9} {:>6.1}%\n", mode, count, share);
+        }
+
+        out += "\nhot spots\n  addr        count       %  instruction\n";
+        for (addr, count) in self.hot_spots(top) {
+            let text = disasm::decode(mem, addr)
+                .map(|(text, _)| text)
+                .unwrap_or_else(|| "?".to_owned());
+            out += &format!(
+                "{:>6} {:>12} {:>6.1}%  {}\n",
+                addr,
+                count,
+                percent(count),
+                text
+            );
+        }
+        out
+    }
+
+    /// Disassembly of `mem` with each line prefixed by how often it was executed
+    pub fn annotate(&self, mem: &[isize]) -> String {
+        disasm::disassemble(mem)
+            .iter()
+            .map(|line| match self.count_at(line.addr) {
+                0 => format!("{:>12}  {}\n", "", line),
+                count => format!("{:>12}  {}\n", count, line),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Machine, State};
+
+    #[test]
+    fn counts_countdown() {
+        // counts down from 3 then outputs and halts
+        let program = vec![1001, 10, -1, 10, 1005, 10, 0, 104, 7, 99, 3];
+        let mut machine = Machine::new(program.clone());
+        machine.set_profiling(true);
+        while machine.run().unwrap() != State::Halted {}
+
+        let profile = machine.profile().unwrap();
+        assert_eq!(profile.steps(), 7);
+        assert_eq!((profile.inputs(), profile.outputs()), (0, 1));
+        assert_eq!(
+            profile.opcode_counts(),
+            vec![("ADD", 3), ("JT", 3), ("OUT", 1)]
+        );
+        assert_eq!(
+            profile.mode_counts(),
+            [("position", 9), ("immediate", 7), ("relative", 0)]
+        );
+        assert_eq!(profile.hot_spots(2), vec![(0, 3), (4, 3)]);
+        assert_eq!(profile.count_at(7), 1);
+
+        let mut total = profile.clone();
+        total.merge(profile);
+        assert_eq!(total.steps(), 14);
+        assert_eq!(total.hot_spots(1), vec![(0, 6)]);
+
+        let report = profile.report(&program, 1);
+        assert!(report.starts_with("steps: 7  inputs: 0  outputs: 1\n"));
+        assert!(report.contains("     0            3   42.9%  ADD [10], #-1, [10]\n"));
+        let annotated = profile.annotate(&program);
+        assert!(annotated.starts_with("           3       0: ADD [10], #-1, [10]"));
+        assert!(annotated.contains(&format!("\n{:14}     9: HALT", "")));
+    }
+}
@@ -0,0 +1,274 @@
+//! Saved state of a paused `Machine`, which can be restored later or written to disk.
+//!
+//! A snapshot covers everything which affects how the program continues: memory, instruction
+//! pointer, relative base, the step count and any queued input. Settings such as limits and
+//! profiling belong to the machine being restored into, not the snapshot.
+//!
+//! The text format starts with a version line, followed by one `key value` line per field:
+//!
+//! ```text
+//! intcode snapshot v1
+//! ip 2
+//! relative_base 0
+//! steps 1
+//! input 7,8
+//! memory 3,9,3,10,99,0
+//! sparse 10000:5
+//! ```
+//!
+//! `memory` holds the dense region starting at address 0, and `sparse` any far off cells as
+//! `address:value` pairs.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{Memory, Word};
+
+/// Version written by `Snapshot`'s `Display` impl, and the only one which can be read
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot<W = isize> {
+    pub memory: Memory<W>,
+    pub ip: usize,
+    pub relative_base: W,
+    pub steps: usize,
+    /// Input queued but not yet consumed
+    pub input: Vec<W>,
+}
+
+/// Problem reading a snapshot, `line` counts from 1 and is 0 for a missing field
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl<W: Word> Snapshot<W> {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+fn join<T: fmt::Display>(vals: impl Iterator<Item = T>) -> String {
+    vals.map(|val| val.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl<W: Word> fmt::Display for Snapshot<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dense = self.memory.as_slice();
+        let sparse: Vec<String> = self
+            .memory
+            .iter()
+            .filter(|(addr, _)| *addr >= dense.len())
+            .map(|(addr, val)| format!("{}:{}", addr, val))
+            .collect();
+        writeln!(f, "intcode snapshot v{}", VERSION)?;
+        writeln!(f, "ip {}", self.ip)?;
+        writeln!(f, "relative_base {}", self.relative_base)?;
+        writeln!(f, "steps {}", self.steps)?;
+        writeln!(f, "input {}", join(self.input.iter()))?;
+        writeln!(f, "memory {}", join(dense.iter()))?;
+        writeln!(f, "sparse {}", sparse.join(" "))
+    }
+}
+
+impl<W: Word> FromStr for Snapshot<W> {
+    type Err = SnapshotError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text.lines().map(str::trim).enumerate();
+        let error = |line: usize, message: String| SnapshotError {
+            line: line + 1,
+            message,
+        };
+        match lines.next() {
+            Some((_, header)) if header == format!("intcode snapshot v{}", VERSION) => (),
+            Some((n, header)) if header.starts_with("intcode snapshot v") => {
+                return Err(error(n, format!("unsupported version '{}'", &header[18..])))
+            }
+            _ => return Err(error(0, "not an intcode snapshot".to_owned())),
+        }
+
+        let (mut ip, mut relative_base, mut steps, mut input, mut memory) =
+            (None, None, None, None, None);
+        let mut sparse = Vec::new();
+        for (n, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            let (key, value) = match line.find(' ') {
+                Some(space) => (&line[..space], line[space + 1..].trim()),
+                None => (line, ""),
+            };
+            let invalid = |what: &str| error(n, format!("invalid {} '{}'", what, value));
+            let list = || -> Result<Vec<W>, SnapshotError> {
+                value
+                    .split(',')
+                    .filter(|val| !val.is_empty())
+                    .map(|val| val.trim().parse().map_err(|_| invalid(key)))
+                    .collect()
+            };
+            match key {
+                "ip" => ip = Some(value.parse().map_err(|_| invalid(key))?),
+                "relative_base" => relative_base = Some(value.parse().map_err(|_| invalid(key))?),
+                "steps" => steps = Some(value.parse().map_err(|_| invalid(key))?),
+                "input" => input = Some(list()?),
+                "memory" => memory = Some(list()?),
+                "sparse" => {
+                    for cell in value.split_whitespace() {
+                        let mut parts = cell.splitn(2, ':');
+                        let addr = parts.next().and_then(|addr| addr.parse().ok());
+                        let val = parts.next().and_then(|val| val.parse().ok());
+                        match (addr, val) {
+                            (Some(addr), Some(val)) => sparse.push((addr, val)),
+                            _ => return Err(invalid("sparse cell")),
+                        }
+                    }
+                }
+                _ => return Err(error(n, format!("unknown field '{}'", key))),
+            }
+        }
+
+        let missing = |field: &str| SnapshotError {
+            line: 0,
+            message: format!("missing field '{}'", field),
+        };
+        let mut memory: Memory<W> = memory
+            .ok_or_else(|| missing("memory"))?
+            .into_iter()
+            .collect();
+        for (addr, val) in sparse {
+            memory.set(addr, val);
+        }
+        Ok(Snapshot {
+            memory,
+            ip: ip.ok_or_else(|| missing("ip"))?,
+            relative_base: relative_base.ok_or_else(|| missing("relative_base"))?,
+            steps: steps.ok_or_else(|| missing("steps"))?,
+            input: input.ok_or_else(|| missing("input"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Machine, State};
+    use super::*;
+
+    /// Reads two values, outputs their sum, then stores 5 far out of the way and outputs it
+    fn program() -> Vec<isize> {
+        vec![
+            3, 100, 3, 101, 1, 100, 101, 102, 4, 102, 1101, 0, 5, 10000, 4, 10000, 99,
+        ]
+    }
+
+    #[test]
+    fn restore_continues_run() {
+        let mut machine = Machine::new(program());
+        machine.push_input(3);
+        assert_eq!(machine.run(), Ok(State::NeedsInput));
+        machine.push_input(4);
+        machine.push_input(9);
+        let snapshot = machine.snapshot();
+        assert_eq!(snapshot.input, vec![4, 9]);
+        assert_eq!(snapshot.ip, 2);
+        assert_eq!(snapshot.steps, 1);
+
+        assert_eq!(machine.run(), Ok(State::Output(7)));
+        assert_eq!(machine.run(), Ok(State::Output(5)));
+        let done = machine.snapshot();
+
+        // restoring rewinds the machine, a fresh machine can be made from one too
+        machine.restore(snapshot.clone());
+        assert_eq!(machine.run(), Ok(State::Output(7)));
+        let mut copy = Machine::from(snapshot);
+        assert_eq!(copy.run(), Ok(State::Output(7)));
+        assert_eq!(copy.run(), Ok(State::Output(5)));
+        assert_eq!(copy.snapshot(), done);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut machine = Machine::new(program());
+        machine.push_input(3);
+        machine.push_input(4);
+        machine.push_input(9);
+        machine.run().unwrap();
+        machine.run().unwrap();
+        let snapshot = machine.snapshot();
+        let text = snapshot.to_string();
+        assert!(text.starts_with("intcode snapshot v1\nip 16\nrelative_base 0\nsteps 6\ninput 9\n"));
+        assert!(text.ends_with("\nsparse 10000:5\n"));
+        let parsed: Snapshot = text.parse().unwrap();
+        assert_eq!(parsed, snapshot);
+
+        let path =
+            std::env::temp_dir().join(format!("intcode_snapshot_test_{}.txt", std::process::id()));
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut restored: Machine = loaded.unwrap().into();
+        assert_eq!(restored.memory()[10000], 5);
+        assert_eq!(restored.run(), Ok(State::Halted));
+
+        // other word types use the same format
+        let wide: Snapshot<i128> = text.parse().unwrap();
+        assert_eq!(wide.memory[10000], 5);
+    }
+
+    #[test]
+    fn round_trip_after_growing() {
+        // writes far past the program, then grows the dense region part of the way there
+        let mut machine = Machine::new(vec![1101, 1, 1, 6000, 1101, 2, 2, 3000, 99]);
+        assert_eq!(machine.run(), Ok(State::Halted));
+        let snapshot = machine.snapshot();
+        let parsed: Snapshot = snapshot.to_string().parse().unwrap();
+        assert_eq!(parsed, snapshot);
+        assert_eq!((parsed.memory[3000], parsed.memory[6000]), (4, 2));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let good = Machine::new(program()).snapshot().to_string();
+        let parse = |text: &str| text.parse::<Snapshot>().unwrap_err().to_string();
+        assert_eq!(
+            parse(&good.replace("v1", "v2")),
+            "line 1: unsupported version '2'"
+        );
+        assert_eq!(parse("hello"), "line 1: not an intcode snapshot");
+        assert_eq!(
+            parse(&good.replace("ip 0", "ip -1")),
+            "line 2: invalid ip '-1'"
+        );
+        assert_eq!(
+            parse(&good.replace("steps 0\n", "")),
+            "line 0: missing field 'steps'"
+        );
+        assert_eq!(
+            parse(&good.replace("sparse", "sparse 1:x")),
+            "line 7: invalid sparse cell '1:x'"
+        );
+        assert_eq!(
+            parse(&format!("{}colour blue\n", good)),
+            "line 8: unknown field 'colour'"
+        );
+    }
+}
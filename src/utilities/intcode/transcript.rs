@@ -0,0 +1,377 @@
+//! Recording a run's I/O, and replaying it to check a program still behaves the same.
+//!
+//! A transcript lists every input consumed and output produced, in order, tagged with the step
+//! (number of instructions executed before it) at which it happened. It is saved as plain text,
+//! one event per line:
+//!
+//! ```text
+//! # intcode transcript
+//! 0 in 5
+//! 12 out 999
+//! 13 halt
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{Input, IntcodeError, Machine, State};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Input { step: usize, val: isize },
+    Output { step: usize, val: isize },
+    Halt { step: usize },
+}
+
+impl Event {
+    pub fn step(&self) -> usize {
+        match self {
+            Event::Input { step, .. } | Event::Output { step, .. } | Event::Halt { step } => *step,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Input { step, val } => write!(f, "{} in {}", step, val),
+            Event::Output { step, val } => write!(f, "{} out {}", step, val),
+            Event::Halt { step } => write!(f, "{} halt", step),
+        }
+    }
+}
+
+/// What the replayed run did where it stopped matching the transcript
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Observed {
+    /// Asked for input, the value isn't known as the transcript didn't expect it
+    Input {
+        step: usize,
+    },
+    Output {
+        step: usize,
+        val: isize,
+    },
+    Halt {
+        step: usize,
+    },
+    Error(IntcodeError),
+}
+
+impl fmt::Display for Observed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Observed::Input { step } => write!(f, "{} in", step),
+            Observed::Output { step, val } => write!(f, "{} out {}", step, val),
+            Observed::Halt { step } => write!(f, "{} halt", step),
+            Observed::Error(err) => write!(f, "error: {}", err),
+        }
+    }
+}
+
+/// First point where a replay differed from its transcript
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Step at which the replayed run did something unexpected
+    pub step: usize,
+    /// Next event in the transcript, `None` if the transcript had ended
+    pub expected: Option<Event>,
+    pub observed: Observed,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "diverged at step {}: expected ", self.step)?;
+        match &self.expected {
+            Some(event) => write!(f, "'{}'", event)?,
+            None => write!(f, "end of transcript")?,
+        }
+        write!(f, ", got '{}'", self.observed)
+    }
+}
+
+impl Error for Divergence {}
+
+/// Problem parsing a transcript, `line` counts from 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for TranscriptError {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transcript {
+    pub events: Vec<Event>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every output value, in order
+    pub fn outputs(&self) -> Vec<isize> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Output { val, .. } => Some(*val),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Run `machine` until it halts, feeding it from `input` and recording its I/O.
+    ///
+    /// Events are appended as they happen, so if the run fails the transcript still covers
+    /// everything up to the error.
+    pub fn record(
+        &mut self,
+        machine: &mut Machine,
+        mut input: impl Input,
+    ) -> Result<(), IntcodeError> {
+        loop {
+            match machine.run()? {
+                State::NeedsInput => {
+                    let val = match input.next_input() {
+                        Some(val) => val,
+                        None => {
+                            // running with nothing to give reports the error
+                            return machine.run_with((), ()).map(|_| ());
+                        }
+                    };
+                    machine.push_input(val);
+                    self.events.push(Event::Input {
+                        step: machine.steps(),
+                        val,
+                    });
+                }
+                State::Output(val) => self.events.push(Event::Output {
+                    step: machine.steps() - 1,
+                    val,
+                }),
+                State::Halted => {
+                    self.events.push(Event::Halt {
+                        step: machine.steps(),
+                    });
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Run `machine`, feeding it the recorded inputs, and check it produces exactly the
+    /// recorded events.
+    pub fn replay(&self, machine: &mut Machine) -> Result<(), Divergence> {
+        let mut expected = self.events.iter().copied();
+        loop {
+            let observed = match machine.run() {
+                Ok(State::NeedsInput) => Observed::Input {
+                    step: machine.steps(),
+                },
+                Ok(State::Output(val)) => Observed::Output {
+                    step: machine.steps() - 1,
+                    val,
+                },
+                Ok(State::Halted) => Observed::Halt {
+                    step: machine.steps(),
+                },
+                Err(err) => Observed::Error(err),
+            };
+            let next = expected.next();
+            let matched = match (next, &observed) {
+                (Some(Event::Input { step, val }), Observed::Input { step: seen }) => {
+                    machine.push_input(val);
+                    step == *seen
+                }
+                (Some(Event::Output { step, val }), Observed::Output { step: s, val: v }) => {
+                    step == *s && val == *v
+                }
+                (Some(Event::Halt { step }), Observed::Halt { step: seen }) => {
+                    if step == *seen {
+                        return Ok(());
+                    }
+                    false
+                }
+                _ => false,
+            };
+            if !matched {
+                let step = match observed {
+                    Observed::Input { step } | Observed::Output { step, .. } => step,
+                    Observed::Halt { step } => step,
+                    Observed::Error(_) => machine.steps(),
+                };
+                return Err(Divergence {
+                    step,
+                    expected: next,
+                    observed,
+                });
+            }
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Transcript> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# intcode transcript")?;
+        for event in self.events.iter() {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the text format, blank lines and lines starting with `#` are ignored
+impl FromStr for Transcript {
+    type Err = TranscriptError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| TranscriptError {
+                line: n + 1,
+                message: format!("{} in '{}'", message, line),
+            };
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let step = parts[0].parse().map_err(|_| error("invalid step"))?;
+            let val = || -> Result<isize, TranscriptError> {
+                match parts.get(2) {
+                    Some(val) if parts.len() == 3 => {
+                        val.parse().map_err(|_| error("invalid value"))
+                    }
+                    _ => Err(error("expected a single value")),
+                }
+            };
+            let event = match parts.get(1) {
+                Some(&"in") => Event::Input { step, val: val()? },
+                Some(&"out") => Event::Output { step, val: val()? },
+                Some(&"halt") if parts.len() == 2 => Event::Halt { step },
+                _ => return Err(error("unknown event")),
+            };
+            events.push(event);
+        }
+        Ok(Transcript { events })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::day05;
+    use super::*;
+
+    #[test]
+    fn record_and_replay() {
+        let mut transcript = Transcript::new();
+        transcript
+            .record(&mut Machine::new(day05()), &[5][..])
+            .unwrap();
+        assert_eq!(transcript.outputs(), vec![8805067]);
+        assert_eq!(transcript.events[0], Event::Input { step: 0, val: 5 });
+
+        let text = transcript.to_string();
+        assert!(text.starts_with("# intcode transcript\n0 in 5\n"));
+        let parsed: Transcript = text.parse().unwrap();
+        assert_eq!(parsed, transcript);
+        assert_eq!(parsed.replay(&mut Machine::new(day05())), Ok(()));
+
+        // changing a constant in the program makes the second self-test fail
+        let mut diagnostics = Transcript::new();
+        diagnostics
+            .record(&mut Machine::new(day05()), &[1][..])
+            .unwrap();
+        let mut mem = day05();
+        mem[17] += 1;
+        assert_eq!(
+            diagnostics.replay(&mut Machine::new(mem)),
+            Err(Divergence {
+                step: 7,
+                expected: Some(Event::Output { step: 7, val: 0 }),
+                observed: Observed::Output { step: 7, val: 1 }
+            })
+        );
+    }
+
+    #[test]
+    fn replay_mismatches() {
+        // reads one value and outputs it doubled
+        let program = vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+        let transcript: Transcript = "0 in 4\n2 out 8\n3 halt\n".parse().unwrap();
+        assert_eq!(
+            transcript.replay(&mut Machine::new(program.clone())),
+            Ok(())
+        );
+
+        let short: Transcript = "0 in 4\n".parse().unwrap();
+        assert_eq!(
+            short.replay(&mut Machine::new(program.clone())),
+            Err(Divergence {
+                step: 2,
+                expected: None,
+                observed: Observed::Output { step: 2, val: 8 }
+            })
+        );
+
+        let no_input: Transcript = "0 out 8\n".parse().unwrap();
+        let err = no_input.replay(&mut Machine::new(program)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "diverged at step 0: expected '0 out 8', got '0 in'"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "# ok\n\n3 in x".parse::<Transcript>(),
+            Err(TranscriptError {
+                line: 3,
+                message: "invalid value in '3 in x'".to_owned()
+            })
+        );
+        assert!("1 halt 2".parse::<Transcript>().is_err());
+        assert!("1 jump".parse::<Transcript>().is_err());
+        assert!("one in 2".parse::<Transcript>().is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let transcript: Transcript = "0 in 4\n2 out 8\n3 halt\n".parse().unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "intcode_transcript_test_{}.txt",
+            std::process::id()
+        ));
+        transcript.save(&path).unwrap();
+        let loaded = Transcript::load(&path);
+        std::fs::write(&path, "0 nope").unwrap();
+        let invalid = Transcript::load(&path);
+        // clean up before checking anything, so a failure doesn't leave the file behind
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), transcript);
+        assert_eq!(invalid.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
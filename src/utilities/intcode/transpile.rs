@@ -0,0 +1,536 @@
+//! Ahead-of-time translation of an IntCode program into Rust source.
+//!
+//! Each basic block of the program's control-flow graph becomes one arm of a `match` on the
+//! instruction pointer, with parameters resolved at translation time wherever their addressing
+//! mode allows. The generated module exposes `run`, which takes the same arguments as `interpret`,
+//! so it can be compiled into the crate and used in place of interpreting the program.
+//!
+//! Besides the code reachable from address 0, the translation covers return addresses which are
+//! pushed as constants before a call, see `graph`.
+//!
+//! The translation assumes the code never changes. A program with a position mode write into its
+//! own code, or into an address control reaches before it holds a valid instruction, is refused,
+//! while relative mode writes are checked as they happen. Whenever generated code meets something
+//! it can't handle (a write into code, a jump to an address it has no block for, an address which
+//! can't be computed) it hands the rest of the run over to the interpreter, which also reports any
+//! errors. Generated code doesn't support `Limits`.
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use super::cfg::{Block, Edge, Graph};
+use super::snapshot::Snapshot;
+use super::{
+    drive, parse_instruction, AddrMode, Input, IntcodeError, Machine, Memory, OpCode, Output,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TranspileError {
+    /// The instruction at `ip` writes through a position mode parameter to `addr`, which is code
+    SelfModifying { ip: usize, addr: usize },
+}
+
+impl fmt::Display for TranspileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TranspileError::SelfModifying { ip, addr } => write!(
+                f,
+                "self-modifying code: instruction at {} writes to code at {}",
+                ip, addr
+            ),
+        }
+    }
+}
+
+impl Error for TranspileError {}
+
+/// Translate the program in `mem` into the source of a Rust module.
+///
+/// The module refers to this crate's `intcode` module, so is meant to live inside the crate.
+pub fn transpile(mem: &[isize]) -> Result<String, TranspileError> {
+    let graph = graph(mem);
+    // control reaching an address which doesn't hold a valid instruction yet is a sure sign the
+    // program means to write one there
+    let code: BTreeSet<usize> = graph
+        .blocks
+        .values()
+        .flat_map(|block| {
+            let targets = block.edges.iter().filter_map(|edge| match edge {
+                Edge::Fallthrough(addr) | Edge::Jump(addr) => Some(*addr),
+                Edge::Unknown => None,
+            });
+            (block.start..block.end).chain(targets)
+        })
+        .collect();
+    for block in graph.blocks.values() {
+        for (ip, _) in block.instructions.iter() {
+            let (op, modes) = decode(mem, *ip);
+            if let Some(n) = op.write_param() {
+                let param = mem[ip + n];
+                let pos = modes[n - 1] == AddrMode::Pos && param >= 0;
+                if pos && code.contains(&(param as usize)) {
+                    return Err(TranspileError::SelfModifying {
+                        ip: *ip,
+                        addr: param as usize,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut gen = Generator::default();
+    let mut arms = String::new();
+    for block in graph.blocks.values() {
+        arms += &gen.block(mem, block);
+    }
+
+    // blocks are straight line runs of code, so neighbouring ones merge into a single range
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for block in graph.blocks.values() {
+        match ranges.last_mut() {
+            Some(last) if last.1 == block.start => last.1 = block.end,
+            _ => ranges.push((block.start, block.end)),
+        }
+    }
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|(start, end)| format!("({}, {})", start, end))
+        .collect();
+    let image: Vec<String> = mem
+        .chunks(16)
+        .map(|chunk| {
+            let words: Vec<String> = chunk.iter().map(|word| word.to_string()).collect();
+            format!("    {},\n", words.join(", "))
+        })
+        .collect();
+    let mutable = |used: bool| if used { "mut " } else { "" };
+
+    let mut out = String::from(concat!(
+        "//! IntCode program translated into Rust by `intcode::transpile`, ",
+        "don't edit by hand.\n\n",
+    ));
+    out += "use crate::utilities::intcode::transpile;\n";
+    out += "use crate::utilities::intcode::{Input, IntcodeError, Memory, Output};\n\n";
+    out += "/// Memory image the program was translated from\n";
+    out += &format!("pub const IMAGE: &[isize] = &[\n{}];\n\n", image.concat());
+    out += "/// Address ranges holding code, as `(start, end)`\n";
+    out += &format!(
+        "const CODE: &[(usize, usize)] = &[{}];\n\n",
+        ranges.join(", ")
+    );
+    out += "/// Run the program in `mem` until it halts, as `intcode::interpret` does.\n";
+    out += "///\n";
+    out += concat!(
+        "/// The interpreter takes over if `mem` holds different code to `IMAGE`, or the program\n",
+        "/// writes to its code.\n",
+    );
+    out += "pub fn run(\n    mem: &mut Memory,\n";
+    out += &format!("    {}input: impl Input,\n", mutable(gen.input));
+    out += &format!("    {}output: impl Output,\n", mutable(gen.output));
+    out += ") -> Result<isize, IntcodeError> {\n";
+    out += "    if !transpile::matches(mem, IMAGE, CODE) {\n";
+    out += "        return transpile::resume(mem, 0, 0, input, output);\n";
+    out += "    }\n";
+    out += "    let mut ip = 0;\n";
+    out += &format!("    let {}rb: isize = 0;\n", mutable(gen.adjusts_base));
+    out += "    // hand the rest of the run over to the interpreter, starting at `$ip`\n";
+    out += "    macro_rules! interpret_from {\n";
+    out += "        ($ip:expr) => {\n";
+    out += "            return transpile::resume(mem, $ip, rb, input, output)\n";
+    out += "        };\n";
+    out += "    }\n";
+    if gen.dynamic {
+        out += "    // unwrap an address, or hand over to the interpreter if there isn't one\n";
+        out += "    macro_rules! or_interpret {\n";
+        out += "        ($addr:expr, $ip:expr) => {\n";
+        out += "            match $addr {\n";
+        out += "                Some(addr) => addr,\n";
+        out += "                None => interpret_from!($ip),\n";
+        out += "            }\n";
+        out += "        };\n";
+        out += "    }\n";
+    }
+    out += "    loop {\n";
+    out += "        match ip {\n";
+    out += &arms;
+    out += "            _ => interpret_from!(ip),\n";
+    out += "        }\n";
+    out += "    }\n";
+    out += "}\n";
+    Ok(out)
+}
+
+/// Control-flow graph of the program, including code only reached by returning from a call.
+///
+/// A constant stored by an ADD or MUL with two immediate parameters is taken to be a return
+/// address pushed before a call when it is the address just after a reachable jump.
+fn graph(mem: &[isize]) -> Graph {
+    let mut entries = vec![0];
+    loop {
+        let graph = Graph::build_from(mem, &entries);
+        let after_jumps: BTreeSet<usize> = graph
+            .blocks
+            .values()
+            .filter(|block| {
+                let (last, _) = block.instructions[block.instructions.len() - 1];
+                let (op, _) = decode(mem, last);
+                op == OpCode::JmpIfTrue || op == OpCode::JmpIfFalse
+            })
+            .map(|block| block.end)
+            .collect();
+        let returns: Vec<usize> = graph
+            .blocks
+            .values()
+            .flat_map(|block| block.instructions.iter())
+            .filter_map(|(ip, _)| {
+                // shorter instructions can end the program, so only read operands ADD and MUL have
+                let val = match decode(mem, *ip) {
+                    (OpCode::Add, [AddrMode::Imm, AddrMode::Imm, _]) => {
+                        mem[ip + 1].wrapping_add(mem[ip + 2])
+                    }
+                    (OpCode::Multiply, [AddrMode::Imm, AddrMode::Imm, _]) => {
+                        mem[ip + 1].wrapping_mul(mem[ip + 2])
+                    }
+                    _ => return None,
+                };
+                usize::try_from(val).ok()
+            })
+            .filter(|addr| after_jumps.contains(addr) && !graph.blocks.contains_key(addr))
+            .collect();
+        if returns.is_empty() {
+            return graph;
+        }
+        entries.extend(returns);
+    }
+}
+
+/// Opcode and parameter modes of an instruction the control-flow graph found
+fn decode(mem: &[isize], ip: usize) -> (OpCode, [AddrMode; 3]) {
+    let (op, mode1, mode2, mode3) = parse_instruction(mem[ip] as i128).unwrap();
+    (op, [mode1, mode2, mode3])
+}
+
+/// A parameter as it appears in generated code
+enum Param {
+    Imm(isize),
+    /// Memory cell expression, e.g. `mem[12]`
+    Cell(String),
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Param::Imm(val) => write!(f, "{}", val),
+            Param::Cell(cell) => write!(f, "{}", cell),
+        }
+    }
+}
+
+/// Tracks which optional parts of the generated function are needed
+#[derive(Default)]
+struct Generator {
+    input: bool,
+    output: bool,
+    adjusts_base: bool,
+    // whether `or_interpret!` is used
+    dynamic: bool,
+}
+
+const INDENT: &str = "                ";
+
+impl Generator {
+    /// Match arm running `block`
+    fn block(&mut self, mem: &[isize], block: &Block) -> String {
+        let mut out = format!("            {} => {{\n", block.start);
+        let mut finished = false;
+        for (ip, text) in block.instructions.iter() {
+            out += &format!("{}// {}: {}\n", INDENT, ip, text);
+            let mut lines = Vec::new();
+            finished = self.instruction(mem, *ip, &mut lines);
+            for line in lines {
+                out += &format!("{}{}\n", INDENT, line);
+            }
+            if finished {
+                break;
+            }
+        }
+        if !finished {
+            out += &format!("{}ip = {};\n", INDENT, block.end);
+        }
+        out += "            }\n";
+        out
+    }
+
+    /// Statements for the instruction at `ip`, returns whether they always leave the block
+    fn instruction(&mut self, mem: &[isize], ip: usize, lines: &mut Vec<String>) -> bool {
+        use OpCode::*;
+
+        let (op, modes) = decode(mem, ip);
+        let mut params = Vec::new();
+        for n in 1..=op.num_params() {
+            let write = op.write_param() == Some(n);
+            match self.param(mem, ip, n, modes[n - 1], write, lines) {
+                Some(param) => params.push(param),
+                None => {
+                    // let the interpreter report the bad address
+                    lines.clear();
+                    lines.push(format!("interpret_from!({});", ip));
+                    return true;
+                }
+            }
+        }
+
+        let next = ip + op.num_params() + 1;
+        let value = match (op, params.first(), params.get(1)) {
+            (Add, Some(Param::Imm(a)), Some(Param::Imm(b))) => a.wrapping_add(*b).to_string(),
+            (Add, Some(a), Some(b)) => format!("isize::wrapping_add({}, {})", a, b),
+            (Multiply, Some(Param::Imm(a)), Some(Param::Imm(b))) => a.wrapping_mul(*b).to_string(),
+            (Multiply, Some(a), Some(b)) => format!("isize::wrapping_mul({}, {})", a, b),
+            (LessThan, Some(Param::Imm(a)), Some(Param::Imm(b))) => {
+                ((*a < *b) as isize).to_string()
+            }
+            (LessThan, Some(a), Some(b)) => format!("({} < {}) as isize", a, b),
+            (Equals, Some(Param::Imm(a)), Some(Param::Imm(b))) => ((*a == *b) as isize).to_string(),
+            (Equals, Some(a), Some(b)) => format!("({} == {}) as isize", a, b),
+            (ReadIn, _, _) => {
+                self.input = true;
+                let exhausted = format!(
+                    "IntcodeError::InputExhausted {{ ip: {}, word: {} }}",
+                    ip, mem[ip]
+                );
+                format!(
+                    "match input.next_input() {{ Some(val) => val, None => return Err({}) }}",
+                    exhausted
+                )
+            }
+            (WriteOut, Some(val), _) => {
+                self.output = true;
+                lines.push(format!("output.write_word({});", val));
+                return false;
+            }
+            (JmpIfTrue, Some(cond), Some(target)) | (JmpIfFalse, Some(cond), Some(target)) => {
+                let target = match target {
+                    Param::Imm(target) if *target >= 0 => target.to_string(),
+                    _ => {
+                        self.dynamic = true;
+                        format!("or_interpret!(transpile::addr({}), {})", target, ip)
+                    }
+                };
+                let test = if op == JmpIfTrue { "!=" } else { "==" };
+                lines.push(match cond {
+                    Param::Imm(cond) if (*cond != 0) == (op == JmpIfTrue) => {
+                        format!("ip = {};", target)
+                    }
+                    Param::Imm(_) => format!("ip = {};", next),
+                    cond => format!(
+                        "ip = if {} {} 0 {{ {} }} else {{ {} }};",
+                        cond, test, target, next
+                    ),
+                });
+                return true;
+            }
+            (AdjustBase, Some(offset), _) => {
+                self.adjusts_base = true;
+                self.dynamic = true;
+                lines.push(format!(
+                    "rb = or_interpret!(rb.checked_add({}), {});",
+                    offset, ip
+                ));
+                return false;
+            }
+            (Halt, _, _) => {
+                lines.push("return Ok(mem[0]);".to_owned());
+                return true;
+            }
+            _ => unreachable!(),
+        };
+        lines.push(format!("{} = {};", params.last().unwrap(), value));
+        false
+    }
+
+    /// Generated form of parameter `n`, `None` if its address is already known to be invalid.
+    ///
+    /// The address of a relative mode parameter is computed into `a{n}` by a statement added to
+    /// `lines`, which for a write also checks it doesn't land in the code.
+    fn param(
+        &mut self,
+        mem: &[isize],
+        ip: usize,
+        n: usize,
+        mode: AddrMode,
+        write: bool,
+        lines: &mut Vec<String>,
+    ) -> Option<Param> {
+        let param = mem[ip + n];
+        match mode {
+            AddrMode::Imm => Some(Param::Imm(param)),
+            AddrMode::Pos if param < 0 => None,
+            AddrMode::Pos => Some(Param::Cell(format!("mem[{}]", param))),
+            AddrMode::Rel => {
+                self.dynamic = true;
+                let addr = match write {
+                    true => format!("transpile::rel_write(rb, {}, CODE)", param),
+                    false => format!("transpile::rel(rb, {})", param),
+                };
+                lines.push(format!("let a{} = or_interpret!({}, {});", n, addr, ip));
+                Some(Param::Cell(format!("mem[a{}]", n)))
+            }
+        }
+    }
+}
+
+// Support used by generated code
+
+/// Address a computed value refers to, `None` if it is negative
+pub fn addr(val: isize) -> Option<usize> {
+    usize::try_from(val).ok()
+}
+
+/// Address of a relative mode parameter, `None` if it can't be computed
+pub fn rel(rb: isize, offset: isize) -> Option<usize> {
+    rb.checked_add(offset).and_then(addr)
+}
+
+/// Address of a relative mode parameter being written, `None` if it can't be computed or it lies
+/// in one of the `code` ranges
+pub fn rel_write(rb: isize, offset: isize, code: &[(usize, usize)]) -> Option<usize> {
+    rel(rb, offset).filter(|addr| !code.iter().any(|(start, end)| start <= addr && addr < end))
+}
+
+/// Whether `mem` holds the same code as `image`
+pub fn matches(mem: &Memory, image: &[isize], code: &[(usize, usize)]) -> bool {
+    code.iter()
+        .flat_map(|(start, end)| *start..*end)
+        .all(|addr| mem[addr] == image[addr])
+}
+
+/// Carry on running the program in `mem` with the interpreter, from `ip` with relative base `rb`
+pub fn resume(
+    mem: &mut Memory,
+    ip: usize,
+    rb: isize,
+    input: impl Input,
+    output: impl Output,
+) -> Result<isize, IntcodeError> {
+    let snapshot = Snapshot {
+        memory: std::mem::take(mem),
+        ip,
+        relative_base: rb,
+        steps: 0,
+        input: Vec::new(),
+    };
+    drive(Machine::from(snapshot), mem, input, output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::asm::assemble;
+    use super::super::{day05, interpret, transpiled};
+    use super::*;
+
+    /// Squares each value read until a 0, then outputs the sum of the squares. Reading -1 instead
+    /// pokes the next value read into the address read after it.
+    fn program() -> Vec<isize> {
+        assemble(
+            "
+                    ARB #stack
+            loop:   IN [n]
+                    JF [n], #done
+                    EQ [n], #-1, [t]
+                    JT [t], #poke
+                    ADD #ret, #0, rb+0
+                    ADD [n], #0, rb+1
+                    ARB #2
+                    JT #1, #square
+            ret:    ARB #-2
+                    OUT rb+1
+                    ADD [sum], rb+1, [sum]
+                    JT #1, #loop
+            poke:   IN [d]
+                    MUL #stack, #-1, [t]
+                    ADD [d], [t], [d]
+                    ARB [d]
+                    IN rb+0
+                    MUL [d], #-1, [d]
+                    ARB [d]
+                    JT #1, #loop
+            square: MUL rb-1, rb-1, rb-1
+                    JT #1, rb-2
+            done:   OUT [sum]
+                    HALT
+            n:      .data 0
+            t:      .data 0
+            d:      .data 0
+            sum:    .data 0
+            stack:  .data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn generated_module() {
+        // if the translation changes on purpose, write the new output over transpiled.rs
+        assert_eq!(
+            transpile(&program()),
+            Ok(include_str!("transpiled.rs").to_owned())
+        );
+    }
+
+    #[test]
+    fn runs_like_interpreter() {
+        let inputs: [&[isize]; 5] = [
+            &[3, 4, 0],
+            // pokes the running total
+            &[-1, 74, 100, 2, 0],
+            // turns the MUL in `square` into an ADD, so the interpreter takes over
+            &[-1, 61, 22201, 3, 5, 0],
+            // runs out of input
+            &[7],
+            // pokes below address 0
+            &[-1, -3, 1],
+        ];
+        let mut image = Memory::from(program());
+        // changed code makes the interpreter run the whole program
+        let mut changed = image.clone();
+        changed[9] = -2;
+        for mem in [image.clone(), changed].iter() {
+            for input in inputs.iter() {
+                let mut expected_mem = mem.clone();
+                let mut expected_output = Vec::new();
+                let expected = interpret(&mut expected_mem, *input, &mut expected_output);
+                let mut mem = mem.clone();
+                let mut output = Vec::new();
+                assert_eq!(transpiled::run(&mut mem, *input, &mut output), expected);
+                assert_eq!(output, expected_output);
+                assert_eq!(mem, expected_mem);
+            }
+        }
+
+        let mut output = Vec::new();
+        transpiled::run(&mut image, &[-1, 61, 22201, 3, 5, 0][..], &mut output).unwrap();
+        assert_eq!(output, vec![6, 10, 16]);
+        assert_eq!(image[61], 22201);
+    }
+
+    #[test]
+    fn program_ending_in_short_instruction() {
+        let module = transpile(&[99]).unwrap();
+        assert!(module.contains("            0 => {\n                // 0: HALT\n"));
+        let module = transpile(&[104, 1, 99]).unwrap();
+        assert!(module.contains("                // 2: HALT\n"));
+    }
+
+    #[test]
+    fn refuses_self_modifying() {
+        // the system ID is added into the instruction at 6
+        let err = transpile(&day05()).unwrap_err();
+        assert_eq!(err, TranspileError::SelfModifying { ip: 2, addr: 6 });
+        assert_eq!(
+            err.to_string(),
+            "self-modifying code: instruction at 2 writes to code at 6"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! IntCode program translated into Rust by `intcode::transpile`, don't edit by hand.
+
+use crate::utilities::intcode::transpile;
+use crate::utilities::intcode::{Input, IntcodeError, Memory, Output};
+
+/// Memory image the program was translated from
+pub const IMAGE: &[isize] = &[
+    109, 75, 3, 71, 1006, 71, 68, 1008, 71, -1, 72, 1005, 72, 38, 21101, 27,
+    0, 0, 21001, 71, 0, 1, 109, 2, 1105, 1, 61, 109, -2, 204, 1, 2001,
+    74, 1, 74, 1105, 1, 2, 3, 73, 1102, 75, -1, 72, 1, 73, 72, 73,
+    9, 73, 203, 0, 1002, 73, -1, 73, 9, 73, 1105, 1, 2, 22202, -1, -1,
+    -1, 2105, 1, -2, 4, 74, 99, 0, 0, 0, 0, 0,
+];
+
+/// Address ranges holding code, as `(start, end)`
+const CODE: &[(usize, usize)] = &[(0, 71)];
+
+/// Run the program in `mem` until it halts, as `intcode::interpret` does.
+///
+/// The interpreter takes over if `mem` holds different code to `IMAGE`, or the program
+/// writes to its code.
+pub fn run(
+    mem: &mut Memory,
+    mut input: impl Input,
+    mut output: impl Output,
+) -> Result<isize, IntcodeError> {
+    if !transpile::matches(mem, IMAGE, CODE) {
+        return transpile::resume(mem, 0, 0, input, output);
+    }
+    let mut ip = 0;
+    let mut rb: isize = 0;
+    // hand the rest of the run over to the interpreter, starting at `$ip`
+    macro_rules! interpret_from {
+        ($ip:expr) => {
+            return transpile::resume(mem, $ip, rb, input, output)
+        };
+    }
+    // unwrap an address, or hand over to the interpreter if there isn't one
+    macro_rules! or_interpret {
+        ($addr:expr, $ip:expr) => {
+            match $addr {
+                Some(addr) => addr,
+                None => interpret_from!($ip),
+            }
+        };
+    }
+    loop {
+        match ip {
+            0 => {
+                // 0: ARB #75
+                rb = or_interpret!(rb.checked_add(75), 0);
+                ip = 2;
+            }
+            2 => {
+                // 2: IN [71]
+                mem[71] = match input.next_input() { Some(val) => val, None => return Err(IntcodeError::InputExhausted { ip: 2, word: 3 }) };
+                // 4: JF [71], #68
+                ip = if mem[71] == 0 { 68 } else { 7 };
+            }
+            7 => {
+                // 7: EQ [71], #-1, [72]
+                mem[72] = (mem[71] == -1) as isize;
+                // 11: JT [72], #38
+                ip = if mem[72] != 0 { 38 } else { 14 };
+            }
+            14 => {
+                // 14: ADD #27, #0, rb+0
+                let a3 = or_interpret!(transpile::rel_write(rb, 0, CODE), 14);
+                mem[a3] = 27;
+                // 18: ADD [71], #0, rb+1
+                let a3 = or_interpret!(transpile::rel_write(rb, 1, CODE), 18);
+                mem[a3] = isize::wrapping_add(mem[71], 0);
+                // 22: ARB #2
+                rb = or_interpret!(rb.checked_add(2), 22);
+                // 24: JT #1, #61
+                ip = 61;
+            }
+            27 => {
+                // 27: ARB #-2
+                rb = or_interpret!(rb.checked_add(-2), 27);
+                // 29: OUT rb+1
+                let a1 = or_interpret!(transpile::rel(rb, 1), 29);
+                output.write_word(mem[a1]);
+                // 31: ADD [74], rb+1, [74]
+                let a2 = or_interpret!(transpile::rel(rb, 1), 31);
+                mem[74] = isize::wrapping_add(mem[74], mem[a2]);
+                // 35: JT #1, #2
+                ip = 2;
+            }
+            38 => {
+                // 38: IN [73]
+                mem[73] = match input.next_input() { Some(val) => val, None => return Err(IntcodeError::InputExhausted { ip: 38, word: 3 }) };
+                // 40: MUL #75, #-1, [72]
+                mem[72] = -75;
+                // 44: ADD [73], [72], [73]
+                mem[73] = isize::wrapping_add(mem[73], mem[72]);
+                // 48: ARB [73]
+                rb = or_interpret!(rb.checked_add(mem[73]), 48);
+                // 50: IN rb+0
+                let a1 = or_interpret!(transpile::rel_write(rb, 0, CODE), 50);
+                mem[a1] = match input.next_input() { Some(val) => val, None => return Err(IntcodeError::InputExhausted { ip: 50, word: 203 }) };
+                // 52: MUL [73], #-1, [73]
+                mem[73] = isize::wrapping_mul(mem[73], -1);
+                // 56: ARB [73]
+                rb = or_interpret!(rb.checked_add(mem[73]), 56);
+                // 58: JT #1, #2
+                ip = 2;
+            }
+            61 => {
+                // 61: MUL rb-1, rb-1, rb-1
+                let a1 = or_interpret!(transpile::rel(rb, -1), 61);
+                let a2 = or_interpret!(transpile::rel(rb, -1), 61);
+                let a3 = or_interpret!(transpile::rel_write(rb, -1, CODE), 61);
+                mem[a3] = isize::wrapping_mul(mem[a1], mem[a2]);
+                // 65: JT #1, rb-2
+                let a2 = or_interpret!(transpile::rel(rb, -2), 65);
+                ip = or_interpret!(transpile::addr(mem[a2]), 65);
+            }
+            68 => {
+                // 68: OUT [74]
+                output.write_word(mem[74]);
+                // 70: HALT
+                return Ok(mem[0]);
+            }
+            _ => interpret_from!(ip),
+        }
+    }
+}
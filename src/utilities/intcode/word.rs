@@ -0,0 +1,125 @@
+//! Integer types the interpreter can run programs with.
+//!
+//! `isize` is the default, but its size depends on the target. `i64` and `i128` give the same
+//! results everywhere, with arithmetic wrapping on overflow. Wrapping any of them in `Checked`
+//! makes overflow an `IntcodeError::Overflow` instead.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// A single memory cell's worth of integer
+pub trait Word:
+    Copy
+    + fmt::Debug
+    + fmt::Display
+    + Default
+    + Eq
+    + Ord
+    + Hash
+    + FromStr<Err = ParseIntError>
+    + Send
+    + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// `self + rhs`, or `None` if it overflowed and overflow is an error for this type
+    fn try_add(self, rhs: Self) -> Option<Self>;
+
+    /// `self * rhs`, or `None` if it overflowed and overflow is an error for this type
+    fn try_mul(self, rhs: Self) -> Option<Self>;
+
+    /// Widen to an `i128`, which can hold any word
+    fn to_i128(self) -> i128;
+
+    /// Narrow from an `i128`, or `None` if the value doesn't fit
+    fn from_i128(val: i128) -> Option<Self>;
+}
+
+/// Makes arithmetic overflow an error rather than wrapping around
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checked<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for Checked<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: FromStr> FromStr for Checked<T> {
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Checked)
+    }
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {$(
+        impl Word for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn try_add(self, rhs: Self) -> Option<Self> {
+                Some(self.wrapping_add(rhs))
+            }
+
+            fn try_mul(self, rhs: Self) -> Option<Self> {
+                Some(self.wrapping_mul(rhs))
+            }
+
+            fn to_i128(self) -> i128 {
+                self as i128
+            }
+
+            fn from_i128(val: i128) -> Option<Self> {
+                <$t>::try_from(val).ok()
+            }
+        }
+
+        impl Word for Checked<$t> {
+            const ZERO: Self = Checked(0);
+            const ONE: Self = Checked(1);
+
+            fn try_add(self, rhs: Self) -> Option<Self> {
+                self.0.checked_add(rhs.0).map(Checked)
+            }
+
+            fn try_mul(self, rhs: Self) -> Option<Self> {
+                self.0.checked_mul(rhs.0).map(Checked)
+            }
+
+            fn to_i128(self) -> i128 {
+                self.0.to_i128()
+            }
+
+            fn from_i128(val: i128) -> Option<Self> {
+                <$t>::from_i128(val).map(Checked)
+            }
+        }
+    )*};
+}
+
+impl_word!(isize, i64, i128);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overflow() {
+        let big = i64::MAX;
+        assert_eq!(big.try_add(1), Some(i64::MIN));
+        assert_eq!(Checked(big).try_add(Checked(1)), None);
+        assert_eq!(Checked(big).try_mul(Checked(-1)), Some(Checked(-big)));
+        assert_eq!(i128::from(big).try_add(1), Some(i128::from(big) + 1));
+
+        assert_eq!(i64::from_i128(i128::from(big) + 1), None);
+        assert_eq!(Checked::<i64>::from_i128(-5), Some(Checked(-5)));
+        assert_eq!("-7".parse(), Ok(Checked(-7i64)));
+        assert_eq!(Checked(12i128).to_string(), "12");
+    }
+}
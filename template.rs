@@ -1,8 +1,8 @@
-pub fn work(lines: &Vec<String>) {
-    println!("Part 1: {}", do_work(&lines));
+pub fn work(lines: &[String]) {
+    println!("Part 1: {}", do_work(lines));
 }
 
-fn do_work(lines: &Vec<String>) -> usize {
+fn do_work(lines: &[String]) -> usize {
 }
 
 #[cfg(test)]